@@ -0,0 +1,8 @@
+#!/bin/sh
+# Builds the modules the integration tests deploy, and the schema used by the
+# concordium-client examples at the end of src/lib.rs. Run it before
+# `cargo test` after every change to a contract.
+set -e
+cd "$(dirname "$0")"
+cargo concordium build --out concordium-out/module.wasm.v1 --schema-out schema.bin
+(cd gona-token && cargo concordium build --out concordium-out/module.wasm.v1)
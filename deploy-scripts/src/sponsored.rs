@@ -20,7 +20,7 @@ use concordium_rust_sdk::{
 };
 use contracts_common::AccountAddress;
 use deployer::{DeployResult, Deployer, InitResult};
-use gonana_concordium_smart_contract::{ListProductParameter, PermitMessage, PermitParam};
+use gonana_concordium_smart_contract::{Currency, ListProductParameter, PermitMessage, PermitParam};
 use std::{
     io::Cursor,
     path::{Path, PathBuf}, str::FromStr, u64::MAX,
@@ -132,6 +132,9 @@ async fn main() -> Result<(), Error> {
     let list_parameter =  ListProductParameter::new( 
         "bagofpotatoes".into(),
         amount,
+        Currency::Ccd,
+        1,
+        "bag".into(),
         Some(farmer),
         "Steven".into()
     ); // Example
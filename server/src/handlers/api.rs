@@ -4,6 +4,7 @@ use actix_web::{get,post,Responder,HttpResponse, web::Json};
 use concordium_base::base::Energy;
 use concordium_contracts_common::OwnedParameter;
 use validator::Validate;
+use anyhow::Context;
 use log::info;
 use concordium_rust_sdk::{
     common::types::Amount,
@@ -18,7 +19,7 @@ use crate::handlers::{errors::MarketplaceError, types::{PlaceOrderParam, ViewOrd
 
 
 const ENERGY: u64 = 60000;
-use crate::handlers::types::{ListProductParam,Deployer,ListProduct, ViewProductParam, PlaceOrder};
+use crate::handlers::types::{ListProductParam,Deployer,ListProduct, ViewProductParam, PlaceOrder, UpdateMerchantAccountParam, WhitelistUpdate};
 
 #[post("/product/list")]
 pub async fn list_product(
@@ -33,6 +34,12 @@ pub async fn list_product(
             let (deployer, mut client) = get_deployer().await.expect("error while getting deployer");
             log::info!("Acquired keys from path.");
             let param = body.0;
+            // The marketplace only accepts listings from accounts registered
+            // for the farmer_id.
+            if let Err(e) = ensure_merchant_account(&deployer, &mut client, &param.farmer_id).await {
+                log::warn!("MerchantAccountError {:#?}.", e);
+                return HttpResponse::BadRequest().body("could not register the server for the farmer")
+            }
             info!("sponsored contract interaction has started");
             let nonce_response = client
                 .get_next_account_sequence_number(&deployer.key.address)
@@ -43,7 +50,7 @@ pub async fn list_product(
                 
             info!("Process Started..........................");
             log::info!("Create payload.");
-            let payload = ListProductParam::new(param.product_id, param.amount, param.wallet, param.farmer_id);
+            let payload = ListProductParam::new(param.product_id, param.amount, param.quantity, param.unit, param.wallet, param.farmer_id);
             //serialize to bytes
             let bytes = concordium_rust_sdk::smart_contracts::common::to_bytes(&payload);
             // check to owned parameter
@@ -144,7 +151,7 @@ pub async fn order(
             let id = body.product_id.clone();
             let req = body.0;   
             let amount = req.amount.clone().parse::<u64>().unwrap();
-            let payload = PlaceOrderParam::new(req.product_id,req.amount,req.buyer_address,req.buyer_id);
+            let payload = PlaceOrderParam::new(req.product_id,req.amount,req.quantity,req.buyer_address,req.buyer_id);
             let nonce_response = client
             .get_next_account_sequence_number(&deployer.key.address)
             .await
@@ -320,6 +327,74 @@ pub async fn get_orders() -> Result<Json<Vec<ViewOrders>>,MarketplaceError>{
 
 
 
+/// Registers the server account for `farmer_id` through
+/// `update_merchant_accounts`, unless it already is, so that the server can
+/// list products on behalf of the farmer. The server account is the admin of
+/// the marketplace, which may register any merchant_id. Waits until the
+/// registration is finalized, so that the listing sent next is accepted.
+async fn ensure_merchant_account(
+    deployer: &Deployer,
+    client: &mut Client,
+    farmer_id: &str,
+) -> Result<(), anyhow::Error> {
+    let account = concordium_rust_sdk::smart_contracts::common::AccountAddress(deployer.key.address.0);
+    let bytes = concordium_rust_sdk::smart_contracts::common::to_bytes(&farmer_id.to_string());
+    let context = ContractContext {
+        invoker: Some(concordium_rust_sdk::types::Address::Account(deployer.key.address)),
+        contract: ContractAddress::new(7637, 0),
+        amount: Amount::zero(),
+        method: smart_contracts::OwnedReceiveName::try_from("gonana_marketplace.view_merchant_accounts".to_string())?,
+        parameter: smart_contracts::OwnedParameter::try_from(bytes)?,
+        energy: Energy { energy: 60000 },
+    };
+    let result = client.invoke_instance(&v2::BlockIdentifier::Best, &context).await?;
+    let accounts: Vec<concordium_rust_sdk::smart_contracts::common::AccountAddress> = match result.response {
+        InvokeContractResult::Success { return_value, .. } => concordium_contracts_common::from_bytes(
+            &return_value.context("view_merchant_accounts returned no value")?.value,
+        )?,
+        InvokeContractResult::Failure { reason, .. } => {
+            anyhow::bail!("view_merchant_accounts failed: {:#?}", reason)
+        }
+    };
+    if accounts.contains(&account) {
+        return Ok(());
+    }
+
+    log::info!("Register the server account for {farmer_id}.");
+    let payload = UpdateMerchantAccountParam {
+        merchant_id: farmer_id.to_string(),
+        update: WhitelistUpdate::Add,
+        account,
+    };
+    let bytes = concordium_rust_sdk::smart_contracts::common::to_bytes(&payload);
+    let payload = transactions::Payload::Update {
+        payload: transactions::UpdateContractPayload {
+            amount: Amount::zero(),
+            address: ContractAddress::new(7637, 0),
+            receive_name: smart_contracts::OwnedReceiveName::try_from("gonana_marketplace.update_merchant_accounts".to_string())?,
+            message: smart_contracts::OwnedParameter::try_from(bytes)?,
+        },
+    };
+    let nonce_response = client.get_next_account_sequence_number(&deployer.key.address).await?;
+    let transaction_expiry_seconds = chrono::Utc::now().timestamp() as u64 + 3600;
+    let tx = transactions::send::make_and_sign_transaction(
+        &deployer.key.keys,
+        deployer.key.address,
+        nonce_response.nonce,
+        concordium_base::common::types::TransactionTime {
+            seconds: transaction_expiry_seconds,
+        },
+        concordium_rust_sdk::types::transactions::send::GivenEnergy::Absolute(Energy {
+            energy: ENERGY,
+        }),
+        payload,
+    );
+    let hash = client.send_block_item(&transactions::BlockItem::AccountTransaction(tx)).await?;
+    let (_block, summary) = client.wait_until_finalized(&hash).await?;
+    anyhow::ensure!(summary.is_success(), "update_merchant_accounts was rejected: {:#?}", summary.is_rejected_account_transaction());
+    Ok(())
+}
+
 async fn get_deployer()->Result<(Deployer,Client),anyhow::Error>{
     let node = "http://node.testnet.concordium.com:20000";
     let endpoint = Endpoint::from_str(node)?;
@@ -8,29 +8,93 @@ use concordium_rust_sdk::smart_contracts::common as concordium_std;
 //use concordium_rust_sdk::smart_contracts::common::{self as contracts_common};
 use std::str::FromStr;
 
+// The types below mirror the wire format of the `gonana_marketplace`
+// contract, keep them in sync with `src/lib.rs`.
+
 #[derive(Debug, concordium_std::Serial,Serialize, concordium_contracts_common::Deserial)]
 pub enum ProductState {
     Listed,
+    SoldOut,
+    Cancelled,
+}
+
+#[derive(Debug, concordium_std::Serial,Serialize, concordium_contracts_common::Deserial)]
+pub enum OrderStatus {
     Escrowed,
+    Shipped,
+    Disputed,
     Confirmed,
-    Cancelled,
+    Refunded,
+    Resolved,
 }
 
-/// The product parameters used to list a product on the blockchain
+/// A CIS-2 token id, serialized like `TokenIdVec`.
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub struct TokenId {
+    #[concordium(size_length = 1)]
+    pub id: Vec<u8>,
+}
+
+/// The currency a product is priced and paid in.
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub enum Currency {
+    Ccd,
+    Cis2 {
+        contract: concordium_std::ContractAddress,
+        token_id: TokenId,
+    },
+}
+
+/// The location of the product details and an optional checksum of them.
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub struct MetadataUrl {
+    #[concordium(size_length = 2)]
+    pub url: String,
+    pub hash: Option<[u8; 32]>,
+}
+
+/// The terms of an auction, given when listing a product.
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub struct AuctionTerms {
+    pub min_increment: Amount,
+    pub end: concordium_std::Timestamp,
+}
+
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub struct Bid {
+    pub bidder: concordium_std::AccountAddress,
+    pub buyer_id: String,
+    pub amount: Amount,
+}
+
+#[derive(Debug, concordium_std::Serial, Serialize, concordium_contracts_common::Deserial)]
+pub struct Auction {
+    pub min_increment: Amount,
+    pub end: concordium_std::Timestamp,
+    pub highest_bid: Option<Bid>,
+}
+
+/// A product listing as returned by the contract
 #[derive(Debug, concordium_std::Serial, Serialize,concordium_contracts_common::Deserial)]
 pub struct ViewProductParam {
     /// The product_id generated off-chain, that signifies the product on chain.
     pub product_id: String, 
-    /// Amount of the product.
+    /// Price of one unit of the product, in `currency`.
     pub amount: Amount,
+    pub currency: Currency,
+    /// Number of units still available to order.
+    pub quantity: u64,
+    /// Unit of measure of the product, e.g. "crate" or "kg".
+    pub unit: String,
     /// Wallet address of the creator, could be None.
     pub wallet: Option<concordium_std::AccountAddress>,
-    /// Hash of the product parameters to prove intergrity. 
-    pub hash: Option<String>,
+    /// Hash of the product metadata.
+    pub hash: Option<[u8; 32]>,
+    pub metadata_url: Option<MetadataUrl>,
     /// Farmer_id generated offchain that shows the id of a user.
     pub farmer_id: String,
-    pub state: ProductState
-
+    pub state: ProductState,
+    pub auction: Option<Auction>,
 }
 
 // The product parameters used to list a product on the blockchain
@@ -42,10 +106,13 @@ pub struct ListProduct {
     /// Amount of the product.
     #[validate(length(min = 1, message = "amount is required"))]
     pub amount: String,
-    /// Wallet address of the creator, could be None.
+    /// Wallet address of the creator receiving the payouts, the marketplace
+    /// rejects listings without one.
     pub wallet: Option<String>,
-    /// Hash of the product parameters to prove intergrity. 
-    pub hash: Option<String>,
+    /// Number of units available to order.
+    pub quantity: u64,
+    /// Unit of measure of the product, e.g. "crate" or "kg".
+    pub unit: String,
     /// Farmer_id generated offchain that shows the id of a user.
     pub farmer_id: String
 }
@@ -56,20 +123,30 @@ pub struct ListProduct {
 pub struct ListProductParam {
     /// The product_id generated off-chain, that signifies the product on chain.
     pub product_id: String, 
-    /// Amount of the product.
+    /// Price of one unit of the product, in `currency`.
     pub amount: Amount,
+    pub currency: Currency,
+    /// Number of units available to order.
+    pub quantity: u64,
+    /// Unit of measure of the product, e.g. "crate" or "kg".
+    pub unit: String,
     /// Wallet address of the creator, could be None.
     pub wallet: Option<concordium_std::AccountAddress>,
-    /// Hash of the product parameters to prove intergrity. 
-    pub hash: Option<String>,
+    /// Hash of the product metadata.
+    pub hash: Option<[u8; 32]>,
+    pub metadata_url: Option<MetadataUrl>,
+    pub auction: Option<AuctionTerms>,
     /// Farmer_id generated offchain that shows the id of a user.
     pub farmer_id: String
 }
 
 impl ListProductParam {
+    /// Products listed by the server are priced in CCD.
     pub fn new(
         product_id:String,
         amount:String,
+        quantity:u64,
+        unit:String,
         wallet:Option<String>,
         farmer_id:String
     ) -> Self {
@@ -83,13 +160,18 @@ impl ListProductParam {
                 Self{
                     product_id,
                     amount,
+                    currency:Currency::Ccd,
+                    quantity,
+                    unit,
                     wallet:Some(farmer),
                     hash:None,
+                    metadata_url:None,
+                    auction:None,
                     farmer_id}
 
             },
             None => {
-                Self{product_id,amount,wallet:None,hash:None,farmer_id}
+                Self{product_id,amount,currency:Currency::Ccd,quantity,unit,wallet:None,hash:None,metadata_url:None,auction:None,farmer_id}
 
             }
         }
@@ -97,12 +179,31 @@ impl ListProductParam {
 }
 
 
+/// Whether to authorise an account for a merchant_id or to revoke it.
+#[derive(Debug, concordium_std::Serial, concordium_contracts_common::Deserial)]
+pub enum WhitelistUpdate {
+    Remove,
+    Add,
+}
+
+/// The parameter of `update_merchant_accounts`, used by the server to
+/// register its own account for a farmer_id.
+#[derive(Debug, concordium_std::Serial, concordium_contracts_common::Deserial)]
+pub struct UpdateMerchantAccountParam {
+    pub merchant_id: String,
+    pub update: WhitelistUpdate,
+    /// Account acting for the merchant.
+    pub account: concordium_std::AccountAddress,
+}
+
 #[derive(Validate, Deserialize, Serialize, )]
 pub struct PlaceOrder {
     pub product_id: String,
     pub buyer_address:Option<String>,
     pub buyer_id: String,
     pub amount: String,
+    /// Number of units to order.
+    pub quantity: u64,
 }
 
 #[derive(Debug, concordium_std::Serial, concordium_contracts_common::Deserial)]
@@ -111,13 +212,15 @@ pub struct PlaceOrderParam {
     pub buyer_address:Option<concordium_std::AccountAddress>,
     pub buyer_id: String,
     pub amount: Amount,
-
+    /// Number of units to order.
+    pub quantity: u64,
 }
 
 impl PlaceOrderParam {
     pub fn new(
         product_id:String,
         amount:String,
+        quantity:u64,
         wallet:Option<String>,
         buyer_id:String
     ) -> Self {
@@ -132,11 +235,12 @@ impl PlaceOrderParam {
                     product_id,
                     amount,
                     buyer_address:Some(farmer),
-                    buyer_id}
+                    buyer_id,
+                    quantity}
 
             },
             None => {
-                Self{product_id,amount,buyer_address:None,buyer_id}
+                Self{product_id,amount,buyer_address:None,buyer_id,quantity}
 
             }
         }
@@ -167,10 +271,22 @@ impl Deployer {
     }
 }
 
+/// An order as returned by the contract
 #[derive(Debug, concordium_std::Serial, Serialize,concordium_contracts_common::Deserial)]
 pub struct ViewOrders {
+    pub id: u64,
+    pub status: OrderStatus,
+    pub currency: Currency,
     pub product_id: String,
+    pub quantity: u64,
+    /// Amount held in escrow for the order.
     pub amount: Amount,
+    /// Platform fee in basis points charged when the order is settled.
+    pub fee_bps: u16,
     pub buyer_address: Option<concordium_std::AccountAddress>,
-    pub buyer_id: String
+    pub buyer_id: String,
+    pub created_at: concordium_std::Timestamp,
+    pub delivery_deadline: concordium_std::Timestamp,
+    pub shipped_at: Option<concordium_std::Timestamp>,
+    pub confirmation_deadline: Option<concordium_std::Timestamp>,
 }
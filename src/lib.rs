@@ -2,6 +2,7 @@
 
 //! # A Concordium V1 smart contract
 
+use concordium_cis2::*;
 use concordium_std::*;
 use core::fmt::Debug;
 use concordium_std::Amount;
@@ -10,10 +11,58 @@ use concordium_std::Amount;
 /// Enum representing the possible states of a product
 #[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone)]
 pub enum ProductState {
+    /// Units are available to order.
     Listed,
+    /// All units are reserved by orders.
+    SoldOut,
+    /// The merchant withdrew the listing.
+    Cancelled,
+}
+
+/// Enum representing the lifecycle of an order
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone, Copy)]
+pub enum OrderStatus {
+    /// The payment is held in escrow until the order is shipped.
     Escrowed,
+    /// The merchant shipped the order, waiting for the buyer to confirm.
+    Shipped,
+    /// The buyer or the merchant disputed the order, waiting for the arbiter.
+    Disputed,
+    /// The escrow was paid to the merchant.
     Confirmed,
-    Cancelled,
+    /// The escrow was sent back to the buyer.
+    Refunded,
+    /// The arbiter split the escrow between the buyer and the merchant.
+    Resolved,
+}
+
+/// Order ids are assigned by the contract from a counter.
+pub type OrderId = u64;
+
+/// Token ID type of CIS-2 tokens accepted by the marketplace. Any token id,
+/// including the `TokenIdUnit` of GONA, can be represented.
+pub type ContractTokenId = TokenIdVec;
+
+/// Token amount type of CIS-2 tokens accepted by the marketplace. Amounts are
+/// LEB128 encoded on the wire, so this reads any CIS-2 amount that fits a u64.
+pub type ContractTokenAmount = TokenAmountU64;
+
+/// Sha256 digest
+pub type Sha256 = [u8; 32];
+
+/// The currencies a product can be priced and paid in. Prices and escrowed
+/// amounts are kept as `Amount`; for a CIS-2 token they count the smallest
+/// unit of the token instead of micro CCD.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone)]
+pub enum Currency {
+    /// CCD attached to `place_order`.
+    Ccd,
+    /// A whitelisted CIS-2 token transferred to the marketplace, see
+    /// `onReceivingCIS2`.
+    Cis2 {
+        contract: ContractAddress,
+        token_id: ContractTokenId,
+    },
 }
 
 
@@ -21,26 +70,166 @@ pub enum ProductState {
 #[derive(Serialize, Clone, SchemaType, PartialEq, Eq, Debug)]
 pub struct ProductListing {
     product_id: String, 
-    /// Amount of the product.
+    /// Price of one unit of the product, in `currency`.
     amount: Amount,
-    /// Wallet address of the creator, could be None.
+    /// Currency the product is priced and paid in.
+    currency: Currency,
+    /// Number of units still available to order.
+    quantity: u64,
+    /// Unit of measure of the product, e.g. "crate" or "kg".
+    unit: String,
+    /// Wallet address of the creator receiving the payouts, always set.
     wallet: Option<AccountAddress>,
-    /// Hash of the product parameters to prove intergrity. 
-    hash: Option<String>,
+    /// Hash of the `ProductMetadata` of the product, see
+    /// `verify_product_hash`.
+    hash: Option<Sha256>,
+    /// Where wallets find the details of the product, with an optional
+    /// checksum of the document.
+    metadata_url: Option<MetadataUrl>,
     /// Farmer_id generated offchain that shows the id of a user.
     merchant_id: String,
     /// The State of Product
-    state: ProductState
+    state: ProductState,
+    /// Set if all units are sold together by auction instead of ordered at
+    /// `amount` per unit.
+    auction: Option<Auction>,
+}
+
+/// An English auction of all units of a listing. The reserve price is the
+/// price of all units at the `amount` of the listing. Auctions are held in
+/// CCD only.
+#[derive(Serialize, Clone, SchemaType, PartialEq, Eq, Debug)]
+pub struct Auction {
+    /// Smallest step by which a bid must exceed the highest bid.
+    min_increment: Amount,
+    /// Time after which no bids are accepted and the auction can be settled.
+    end: Timestamp,
+    /// The highest bid so far, held in escrow by the marketplace.
+    highest_bid: Option<Bid>,
+}
+
+/// A bid in an auction.
+#[derive(Serialize, Clone, SchemaType, PartialEq, Eq, Debug)]
+pub struct Bid {
+    /// Account that placed the bid and is refunded if outbid.
+    bidder: AccountAddress,
+    /// The buyer_id the order is placed for if the bid wins.
+    buyer_id: String,
+    /// The escrowed amount.
+    amount: Amount,
+}
+
+/// The terms of an auction, given when listing a product.
+#[derive(Serialize, Clone, SchemaType, PartialEq, Eq, Debug)]
+pub struct AuctionTerms {
+    /// Smallest step by which a bid must exceed the highest bid.
+    pub min_increment: Amount,
+    /// Time after which no bids are accepted and the auction can be settled.
+    pub end: Timestamp,
 }
 
 
-// Struct to represent an order.
-#[derive( Serialize, SchemaType, Eq, PartialEq, PartialOrd, Clone)]
+/// Time a merchant has to ship an order after it was placed. After this
+/// deadline the buyer can reclaim the escrowed funds of an unshipped order.
+pub const DELIVERY_PERIOD: Duration = Duration::from_days(14);
+
+/// Time a buyer has to confirm or complain about an order after it was
+/// shipped. After this window the merchant can claim the payout.
+pub const CONFIRMATION_WINDOW: Duration = Duration::from_days(7);
+
+// Struct to represent an order. Orders are kept after they are settled.
+#[derive( Serialize, SchemaType, Eq, PartialEq, Clone, Debug)]
 pub struct Order {
+    pub id: OrderId,
+    pub status: OrderStatus,
+    pub currency: Currency,
     pub product_id: String,
+    /// Number of units ordered.
+    pub quantity: u64,
+    /// Amount held in escrow for the order.
     pub amount: Amount,
+    /// Platform fee in basis points at the time the order was placed, which
+    /// is the fee deducted when it is settled.
+    pub fee_bps: u16,
     pub buyer_address: Option<AccountAddress>,
-    pub buyer_id: String
+    pub buyer_id: String,
+    /// Time the order was placed.
+    pub created_at: Timestamp,
+    /// Time until which the merchant has to ship the order.
+    pub delivery_deadline: Timestamp,
+    /// Time the merchant marked the order as shipped.
+    pub shipped_at: Option<Timestamp>,
+    /// Time until which the buyer can confirm the shipped order, after which
+    /// the merchant can claim the payout.
+    pub confirmation_deadline: Option<Timestamp>,
+}
+
+impl ProductListing {
+    /// The price of `quantity` units of the product.
+    fn price_of(&self, quantity: u64) -> Result<Amount, MarketplaceError> {
+        ensure!(quantity > 0, MarketplaceError::InvalidQuantity);
+        let micro_ccd = self.amount.micro_ccd.checked_mul(quantity).ok_or(MarketplaceError::InvalidPrice)?;
+        Ok(Amount::from_micro_ccd(micro_ccd))
+    }
+
+    /// Take `quantity` units out of the available stock. The listing is sold
+    /// out once no units are left.
+    fn reserve(&mut self, quantity: u64) -> Result<(), MarketplaceError> {
+        ensure!(quantity > 0, MarketplaceError::InvalidQuantity);
+        self.quantity = self.quantity.checked_sub(quantity).ok_or(MarketplaceError::InsufficientStock)?;
+        if self.quantity == 0 {
+            self.state = ProductState::SoldOut;
+        }
+        Ok(())
+    }
+
+    /// Return `quantity` units of a refunded order to the available stock.
+    fn restock(&mut self, quantity: u64) {
+        self.quantity += quantity;
+        if self.state == ProductState::SoldOut && self.quantity > 0 {
+            self.state = ProductState::Listed;
+        }
+    }
+}
+
+impl Order {
+    /// Creates an order escrowing `amount` of `currency`, placed at `now`,
+    /// which has to be shipped within the `DELIVERY_PERIOD`. `fee_bps` is
+    /// the platform fee charged when it is settled.
+    fn new(
+        id: OrderId,
+        params: PlaceOrderParameter,
+        currency: Currency,
+        amount: Amount,
+        fee_bps: u16,
+        now: Timestamp,
+    ) -> Self {
+        Self {
+            id,
+            status: OrderStatus::Escrowed,
+            currency,
+            product_id: params.product_id,
+            quantity: params.quantity,
+            amount,
+            fee_bps,
+            buyer_address: params.buyer_address,
+            buyer_id: params.buyer_id,
+            created_at: now,
+            delivery_deadline: deadline_after(now, DELIVERY_PERIOD),
+            shipped_at: None,
+            confirmation_deadline: None,
+        }
+    }
+
+    /// Whether the escrow of the order has not been released yet.
+    fn is_open(&self) -> bool {
+        matches!(self.status, OrderStatus::Escrowed | OrderStatus::Shipped | OrderStatus::Disputed)
+    }
+}
+
+/// Adds `period` to `now`, saturating at the largest timestamp.
+fn deadline_after(now: Timestamp, period: Duration) -> Timestamp {
+    now.checked_add(period).unwrap_or_else(|| Timestamp::from_timestamp_millis(u64::MAX))
 }
 
 
@@ -51,6 +240,8 @@ pub struct PlaceOrderParameter {
     pub buyer_address:Option<AccountAddress>,
     pub buyer_id: String,
     pub amount: Amount,
+    /// Number of units to order.
+    pub quantity: u64,
 
 }
 
@@ -71,19 +262,117 @@ pub enum MarketplaceError {
     WrongContract,
     Expired,
     WrongFunctionCall,
-    WrongSignature
+    WrongSignature,
+    LogFull,
+    LogMalformed,
+    MissingBuyerAddress,
+    Unauthorized,
+    OrderAlreadyShipped,
+    OrderNotShipped,
+    DeadlinePassed,
+    DeadlineNotReached,
+    InvalidPayoutSplit,
+    /// Sender is neither the buyer of the order nor an approved relayer.
+    UnauthorizedConfirmation,
+    /// Admins cannot revoke their own `Admin` role.
+    CannotRevokeOwnAdmin,
+    /// The fee is larger than `MAX_FEE_BPS`.
+    InvalidFee,
+    /// The quantity is zero.
+    InvalidQuantity,
+    /// The listing has fewer units available than ordered.
+    InsufficientStock,
+    /// The order is not in a valid status for the operation.
+    InvalidOrderStatus,
+    /// The token is not on the token whitelist.
+    UnsupportedToken,
+    /// Invoking the token contract failed.
+    InvokeContractError,
+    /// The payment is not in the currency of the listing.
+    WrongCurrency,
+    /// Upgrade failed because the new module does not exist.
+    FailedUpgradeMissingModule,
+    /// Upgrade failed because the new module does not contain a contract with a
+    /// matching name.
+    FailedUpgradeMissingContract,
+    /// Upgrade failed because the smart contract version of the module is not
+    /// supported.
+    FailedUpgradeUnsupportedModuleVersion,
+    /// The stored state has a layout version `migrate` cannot convert.
+    UnsupportedStateVersion,
+    /// The marketplace is paused.
+    ContractPaused,
+    /// A product with this id has already been listed.
+    ProductAlreadyExists,
+    /// Sender or signer is not an account authorised for the merchant_id.
+    UnauthorizedMerchant,
+    /// The price of a sponsored order could not be pulled from the signer.
+    BuyerNotFunded,
+    /// The product is sold by auction and cannot be ordered.
+    AuctionListing,
+    /// The product is not sold by auction.
+    NotAnAuction,
+    /// The auction ended, no more bids are accepted.
+    AuctionEnded,
+    /// The auction has not ended yet.
+    AuctionNotEnded,
+    /// The bid is below the reserve price or the minimum increment.
+    BidTooLow,
+    /// The auction ended without bids.
+    NoBids,
+    /// The auction has a bid, so it can only be settled.
+    AuctionHasBids,
+    /// The listing has no wallet to pay the merchant out to.
+    MissingWallet,
+}
+
+/// Mapping the logging errors to MarketplaceError.
+impl From<LogError> for MarketplaceError {
+    fn from(le: LogError) -> Self {
+        match le {
+            LogError::Full => Self::LogFull,
+            LogError::Malformed => Self::LogMalformed,
+        }
+    }
+}
+
+/// Mapping errors related to contract upgrades to MarketplaceError.
+impl From<UpgradeError> for MarketplaceError {
+    #[inline(always)]
+    fn from(ue: UpgradeError) -> Self {
+        match ue {
+            UpgradeError::MissingModule => Self::FailedUpgradeMissingModule,
+            UpgradeError::MissingContract => Self::FailedUpgradeMissingContract,
+            UpgradeError::UnsupportedModuleVersion => Self::FailedUpgradeUnsupportedModuleVersion,
+        }
+    }
+}
+
+/// Mapping errors related to contract invocations to MarketplaceError.
+impl<T> From<CallContractError<T>> for MarketplaceError {
+    fn from(_cce: CallContractError<T>) -> Self { Self::InvokeContractError }
 }
 
 /// The parameter used to list product on the blockchain.
 #[derive(Serialize, SchemaType)]
 pub struct ListProductParameter{
     product_id: String, 
-    /// Amount of the product.
+    /// Price of one unit of the product, in `currency`.
     amount: Amount,
-    /// Wallet address of the creator, could be None.
+    /// Currency the product is priced and paid in.
+    currency: Currency,
+    /// Number of units available to order.
+    quantity: u64,
+    /// Unit of measure of the product, e.g. "crate" or "kg".
+    unit: String,
+    /// Wallet address of the creator receiving the payouts, must be set.
     wallet: Option<AccountAddress>,
-    /// Hash of the product parameters to prove intergrity. 
-    hash: Option<String>,
+    /// Hash of the `ProductMetadata` of the product.
+    hash: Option<Sha256>,
+    /// Where wallets find the details of the product.
+    metadata_url: Option<MetadataUrl>,
+    /// Sell all units by auction instead of at `amount` per unit.
+    auction: Option<AuctionTerms>,
     /// Farmer_id generated offchain that shows the id of a user.
     merchant_id: String
 }
@@ -92,27 +381,82 @@ impl ListProductParameter {
     pub fn new(
         product_id:String,
         amount:Amount,
+        currency:Currency,
+        quantity:u64,
+        unit:String,
         wallet:Option<AccountAddress>,
         merchant_id:String
     ) -> Self {
-        Self{product_id,amount,wallet,hash:None,merchant_id}
+        Self{product_id,amount,currency,quantity,unit,wallet,hash:None,metadata_url:None,auction:None,merchant_id}
+    }
+
+    /// Sell all units by auction, with the price of all units at `amount` as
+    /// reserve price.
+    pub fn with_auction(self, min_increment: Amount, end: Timestamp) -> Self {
+        Self {
+            auction: Some(AuctionTerms {
+                min_increment,
+                end,
+            }),
+            ..self
+        }
+    }
+
+    /// Set the URL where wallets find the details of the product.
+    pub fn with_metadata_url(self, metadata_url: MetadataUrl) -> Self {
+        Self {
+            metadata_url: Some(metadata_url),
+            ..self
+        }
     }
-    // just a stupid implementation, in reality all the information will be hashed and sent to the blockchain
-    pub fn hash(&self,crypto_primitives: &impl HasCryptoPrimitives) -> Self {
-        let payload = self;
-        let bytes = to_bytes(payload);
-        let hash = crypto_primitives.hash_sha2_256(&bytes).to_string();
+
+    /// Set the hash of the product to the hash of its catalogue entry.
+    pub fn with_metadata(self, metadata: &ProductMetadata, crypto_primitives: &impl HasCryptoPrimitives) -> Self {
         Self {
-            product_id: self.product_id.clone(),
-            amount: self.amount,
-            wallet: self.wallet,
-            hash: Some(hash),
-            merchant_id: self.merchant_id.clone(),
+            hash: Some(metadata.hash(crypto_primitives)),
+            ..self
         }
+    }
+}
+
+/// The canonical catalogue entry of a product, kept off-chain. A listing
+/// commits to it with `ProductMetadata::hash`, which is computed the same way
+/// off-chain: the SHA-256 digest of the fields serialized in the order
+/// `name`, `grade`, `origin`, `harvest_date`, `photos`, where
+/// - strings are UTF-8 bytes prefixed by their length as a little-endian u32,
+/// - `harvest_date` is milliseconds since the Unix epoch as a little-endian
+///   u64,
+/// - `photos` is prefixed by its number of entries as a u8.
+#[derive(Serialize, SchemaType, Clone, PartialEq, Eq, Debug)]
+pub struct ProductMetadata {
+    /// Name of the product, e.g. "Premium cocoa beans".
+    pub name: String,
+    /// Quality grade of the product, e.g. "Grade 1".
+    pub grade: String,
+    /// Where the product was grown, e.g. "Ondo, Nigeria".
+    pub origin: String,
+    /// When the product was harvested, in milliseconds since the Unix epoch.
+    pub harvest_date: Timestamp,
+    /// URLs or content hashes of the photos of the product.
+    #[concordium(size_length = 1)]
+    pub photos: Vec<String>,
+}
 
+impl ProductMetadata {
+    /// The hash a listing stores for this catalogue entry.
+    pub fn hash(&self, crypto_primitives: &impl HasCryptoPrimitives) -> Sha256 {
+        crypto_primitives.hash_sha2_256(&to_bytes(self)).0
     }
 }
 
+/// The parameter type for the contract function `verify_product_hash`.
+#[derive(Serialize, SchemaType)]
+pub struct VerifyProductHashParameter {
+    pub product_id: String,
+    /// The catalogue entry to check against the listing.
+    pub metadata: ProductMetadata,
+}
+
 
 
 #[derive(Serialize, SchemaType)]
@@ -121,563 +465,3105 @@ pub struct CancelProductParameter{
     pub merchant_id: String
 }
 
+/// The parameter used by a merchant to update a listed product. Fields that
+/// are `None` are left unchanged.
+#[derive(Serialize, SchemaType)]
+pub struct UpdateProductParameter{
+    pub product_id: String,
+    pub merchant_id: String,
+    /// New price of one unit of the product.
+    pub amount: Option<Amount>,
+    /// New wallet address receiving the payouts.
+    pub wallet: Option<AccountAddress>,
+    /// New hash of the `ProductMetadata` of the product.
+    pub hash: Option<Sha256>
+}
 
+/// The parameter used by a merchant to mark an order as shipped.
+#[derive(Serialize, SchemaType)]
+pub struct ShipOrderParameter{
+    pub order_id: OrderId,
+    pub merchant_id: String
+}
 
+/// The parameter identifying an order.
+#[derive(Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct OrderParameter{
+    pub order_id: OrderId
+}
 
+/// How a batch entrypoint treats items that fail.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone, Copy)]
+pub enum BatchMode {
+    /// The whole batch is rejected if any item fails.
+    AllOrNothing,
+    /// Failing items are skipped and reported, the others are applied.
+    BestEffort,
+}
 
+/// The outcome of one item of a batch entrypoint.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub enum BatchItemResult {
+    /// The item was applied.
+    Ok,
+    /// The item was skipped, with the error it failed with.
+    Failed(MarketplaceError),
+}
 
-/// List of supported entrypoints by the `permit` function (CIS3 standard).
-//const SUPPORTS_PERMIT_ENTRYPOINTS: [EntrypointName; 2] =
-    //[EntrypointName::new_unchecked("updateOperator"), EntrypointName::new_unchecked("list_product")];
+/// The parameter type for the contract function `list_products`.
+#[derive(Serialize, SchemaType)]
+pub struct ListProductsParameter {
+    pub mode: BatchMode,
+    #[concordium(size_length = 2)]
+    pub products: Vec<ListProductParameter>,
+}
 
-/// Tag for the CIS3 Nonce event.
-pub const NONCE_EVENT_TAG: u8 = u8::MAX - 5;
+/// The parameter type for the contract function `confirm_orders`.
+#[derive(Serialize, SchemaType)]
+pub struct ConfirmOrdersParameter {
+    pub mode: BatchMode,
+    #[concordium(size_length = 2)]
+    pub order_ids: Vec<OrderId>,
+}
 
-/// Tagged events to be serialized for the event log.
-#[derive(Debug, Serial, Deserial, PartialEq, Eq)]
-#[concordium(repr(u8))]
-pub enum Event {
-    /// The event tracks the nonce used by the signer of the `PermitMessage`
-    /// whenever the `permit` function is invoked.
-    #[concordium(tag = 250)]
-    Nonce(NonceEvent),
+/// The parameter used by the buyer or the merchant to dispute an order.
+#[derive(Serialize, SchemaType)]
+pub struct OpenDisputeParameter{
+    pub order_id: OrderId,
+    /// Short description of the problem with the order.
+    pub reason: String
 }
 
-/// The NonceEvent is logged when the `permit` function is invoked. The event
-/// tracks the nonce used by the signer of the `PermitMessage`.
-#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
-pub struct NonceEvent {
-    /// Account that signed the `PermitMessage`.
-    pub account: AccountAddress,
-    /// The nonce that was used in the `PermitMessage`.
-    pub nonce:   u64,
+/// The parameter used by the arbiter to settle a disputed order. The part of
+/// the escrowed amount not returned to the buyer is paid to the merchant.
+#[derive(Serialize, SchemaType)]
+pub struct ResolveDisputeParameter{
+    pub order_id: OrderId,
+    /// Amount of the escrow sent back to the buyer.
+    pub buyer_amount: Amount
 }
 
+/// Basis points in a whole settled amount.
+pub const BPS_PER_UNIT: u16 = 10_000;
 
-// Implementing a custom schemaType to the `Event` combining all CIS2/CIS3
-// events.
-impl schema::SchemaType for Event {
-    fn get_type() -> schema::Type {
-        let mut event_map = collections::BTreeMap::new();
-        event_map.insert(
-            NONCE_EVENT_TAG,
-            (
-                "Nonce".to_string(),
-                schema::Fields::Named(vec![
-                    (String::from("account"), AccountAddress::get_type()),
-                    (String::from("nonce"), u64::get_type()),
-                ]),
-            ),
-        );
-        schema::Type::TaggedEnum(event_map)
-    }
+/// The largest platform fee, in basis points of a settled amount, i.e. 10%.
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// The platform fee at `fee_bps` of a settled amount.
+fn fee_of(amount: Amount, fee_bps: u16) -> Amount {
+    let fee = u128::from(amount.micro_ccd) * u128::from(fee_bps) / u128::from(BPS_PER_UNIT);
+    // The fee is at most `amount`, so it fits into a u64.
+    Amount::from_micro_ccd(fee as u64)
 }
 
+/// The parameter type for the contract function `setImplementors`.
+/// Takes a standard identifier and list of contract addresses providing
+/// implementations of this standard.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct SetImplementorsParams {
+    /// The identifier for the standard.
+    pub id:           StandardIdentifierOwned,
+    /// The addresses of the implementors of the standard.
+    pub implementors: Vec<ContractAddress>,
+}
 
-/// Part of the parameter type for the contract function `permit`.
-/// Specifies the message that is signed.
-#[derive(SchemaType, Serialize)]
-pub struct PermitMessage {
-    /// The contract_address that the signature is intended for.
-    pub contract_address: ContractAddress,
-    /// A nonce to prevent replay attacks.
-    pub nonce:            u64,
-    /// A timestamp to make signatures expire.
-    pub timestamp:        Timestamp,
-    /// The entry_point that the signature is intended for.
-    pub entry_point:      OwnedEntrypointName,
-    /// The serialized payload that should be forwarded to either the `transfer`
-    /// or the `updateOperator` function.
+/// The parameter type for the contract function `supportsPermit`.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct SupportsPermitQueryParams {
+    /// The list of entrypoints to query.
     #[concordium(size_length = 2)]
-    pub payload:          Vec<u8>,
+    pub queries: Vec<OwnedEntrypointName>,
 }
 
-/// The parameter type for the contract function `permit`.
-/// Takes a signature, the signer, and the message that was signed.
+/// The parameter type for the contract function `setPaused`.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+#[concordium(transparent)]
+pub struct SetPausedParams {
+    /// Contract is paused if `paused = true` and unpaused if `paused = false`.
+    pub paused: bool,
+}
+
+/// The parameter type for the contract function `upgrade`.
+/// Takes the new module and optionally an entrypoint to call in the new module
+/// after triggering the upgrade. The upgrade is reverted if the entrypoint
+/// fails. This is useful for doing migration in the same transaction triggering
+/// the upgrade.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct UpgradeParams {
+    /// The new module reference.
+    pub module:  ModuleReference,
+    /// Optional entrypoint to call in the new module after upgrade.
+    pub migrate: Option<(OwnedEntrypointName, OwnedParameter)>,
+}
+
+/// The parameter type for the contract function `set_fee`.
 #[derive(Serialize, SchemaType)]
-pub struct PermitParam {
-    /// Signature/s. The CIS3 standard supports multi-sig accounts.
-    pub signature: AccountSignatures,
-    /// Account that created the above signature.
-    pub signer:    AccountAddress,
-    /// Message that was signed.
-    pub message:   PermitMessage,
+#[concordium(transparent)]
+pub struct SetFeeParameter{
+    /// Platform fee in basis points of every settled amount.
+    pub fee_bps: u16
 }
 
-#[derive(Serialize)]
-pub struct PermitParamPartial {
-    /// Signature/s. The CIS3 standard supports multi-sig accounts.
-    signature: AccountSignatures,
-    /// Account that created the above signature.
-    signer:    AccountAddress,
+/// The parameter type for the contract function `withdraw_fees`.
+#[derive(Serialize, SchemaType)]
+pub struct WithdrawFeesParameter{
+    /// Currency of the fees to withdraw.
+    pub currency: Currency,
+    /// Account receiving the fees.
+    pub receiver: AccountAddress,
+    /// Amount of the accrued fees to withdraw.
+    pub amount: Amount
 }
 
+/// Whether to add an entry to or remove it from a whitelist, i.e. the token
+/// whitelist or the accounts of a merchant.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone, Copy)]
+pub enum WhitelistUpdate {
+    /// Remove the entry from the whitelist.
+    Remove,
+    /// Add the entry to the whitelist.
+    Add,
+}
 
+/// The parameter type for the contract function `bid`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct BidParameter{
+    pub product_id: String,
+    /// The buyer_id the order is placed for if the bid wins.
+    pub buyer_id: String
+}
 
+/// The parameter type for the contract function `settle_auction`.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct SettleAuctionParameter{
+    pub product_id: String
+}
 
+/// The parameter type for the contract function `set_product_metadata_url`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct SetProductMetadataUrlParameter{
+    pub product_id: String,
+    pub merchant_id: String,
+    /// The new metadata URL, `None` removes it.
+    pub metadata_url: Option<MetadataUrl>
+}
 
+/// The parameter type for the contract function `update_merchant_accounts`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct UpdateMerchantAccountParameter{
+    pub merchant_id: String,
+    /// Whether to authorise the account or to revoke its authorisation.
+    pub update: WhitelistUpdate,
+    /// Account acting for the merchant.
+    pub account: AccountAddress
+}
 
+/// The parameter type for the contract function `update_token_whitelist`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct UpdateTokenWhitelistParameter{
+    /// Whether to add or remove the token.
+    pub update: WhitelistUpdate,
+    /// The CIS-2 contract of the token.
+    pub contract: ContractAddress,
+    /// The id of the token in `contract`.
+    pub token_id: ContractTokenId
+}
 
+/// The return type for the contract function `view_fees`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct FeesView{
+    /// Platform fee in basis points of every settled amount.
+    pub fee_bps: u16,
+    /// Fees kept in the treasury that have not been withdrawn yet, per
+    /// currency.
+    pub accrued: Vec<(Currency, Amount)>
+}
 
+/// The largest number of items returned by a page of the paginated views.
+pub const MAX_PAGE_SIZE: u32 = 100;
 
+/// The largest number of items a page of the paginated views looks at. A page
+/// that reaches it is returned with fewer than `limit` items and a
+/// `next_cursor`, so a filter that rarely matches takes several calls instead
+/// of one that walks the whole state.
+pub const MAX_PAGE_SCAN: u32 = 1_000;
 
+/// The parameter type for the contract function `view_product_listings_page`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ViewProductListingsParameter{
+    /// Position to continue from, `0` for the first page or the
+    /// `next_cursor` of the previous page.
+    pub cursor: u64,
+    /// Maximum number of listings to return, at most `MAX_PAGE_SIZE`.
+    pub limit: u32,
+    /// Only return listings in this state.
+    pub state: Option<ProductState>,
+    /// Only return listings of this merchant.
+    pub merchant_id: Option<String>
+}
 
+/// The return type for the contract function `view_product_listings_page`.
+#[derive(Serialize, SchemaType)]
+pub struct ProductListingsPage{
+    pub listings: Vec<ProductListing>,
+    /// The cursor of the next page, `None` if this is the last page.
+    pub next_cursor: Option<u64>
+}
 
+/// The parameter type for the contract function `view_orders_page`.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ViewOrdersParameter{
+    /// Position to continue from, `0` for the first page or the
+    /// `next_cursor` of the previous page.
+    pub cursor: u64,
+    /// Maximum number of orders to return, at most `MAX_PAGE_SIZE`.
+    pub limit: u32,
+    /// Only return orders with this status.
+    pub status: Option<OrderStatus>,
+    /// Only return orders placed by this buyer_id.
+    pub buyer_id: Option<String>
+}
 
+/// The return type for the contract function `view_orders_page`.
+#[derive(Serialize, SchemaType)]
+pub struct OrdersPage{
+    pub orders: Vec<Order>,
+    /// The cursor of the next page, `None` if this is the last page.
+    pub next_cursor: Option<u64>
+}
 
+/// The roles that can be granted to addresses of the marketplace.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq, Clone, Copy)]
+pub enum Role {
+    /// Manages roles and the settings of the marketplace.
+    Admin,
+    /// Confirms orders on behalf of buyers.
+    Relayer,
+    /// Resolves disputed orders.
+    Arbiter,
+    /// Refunds orders on behalf of the support team.
+    Moderator,
+}
 
-/// Smart contract state
-#[derive(Serial, DeserialWithState)]
-#[concordium(state_parameter = "S")]
-pub struct State<S = StateApi>  {
-    pub product_listings: StateMap<String,ProductListing, S>,
-    pub orders: StateMap<String,Order,S>,
-    nonces_registry:  StateMap<AccountAddress, u64, S>,
+/// All roles, in the order they are reported by `getRoles`.
+pub const ROLES: [Role; 4] = [Role::Admin, Role::Relayer, Role::Arbiter, Role::Moderator];
 
+/// The parameter type for the contract functions `grantRole`, `revokeRole`
+/// and `hasRole`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RoleParameter{
+    pub address: Address,
+    pub role: Role
 }
 
-// impl State{
+/// The parameter used by a merchant to refund an order.
+#[derive(Serialize, SchemaType)]
+pub struct RefundOrderParameter{
+    pub order_id: OrderId,
+    pub merchant_id: String
+}
 
-//     fn list_product(&mut self, farmer: AccountAddress, product: String, price: Amount) -> Result<(),MarketplaceError>{
-//         let listing = ProductListing {
-//             farmer,
-//             product: product.clone(),
-//             price,
-//             state:ProductState::Listed
-//         };
-//         self.product_listings.insert(product,listing);
-//         Ok(())
-//     }
 
-// }
 
 
 
+/// The standard identifier for the CIS-3: Permit standard.
+pub const CIS3_STANDARD_IDENTIFIER: StandardIdentifier<'static> =
+    StandardIdentifier::new_unchecked("CIS-3");
 
-// Init function to initialize the marketplace state
-#[init(contract = "gonana_marketplace")]
-fn init(_ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<State>{
-    Ok(State { 
-            product_listings: state_builder.new_map(),
-            orders: state_builder.new_map(),
-            nonces_registry:  state_builder.new_map(),
-     })
-}
+/// List of supported standards by this contract address.
+pub const SUPPORTS_STANDARDS: [StandardIdentifier<'static>; 2] =
+    [CIS0_STANDARD_IDENTIFIER, CIS3_STANDARD_IDENTIFIER];
 
-// internal list function that will be executed by the permit message
-fn internal_list_product(host: &mut Host<State>,params:ListProductParameter) -> Result<(),MarketplaceError>{
-    let (state, _builder) = host.state_and_builder();
-    
-    let listing = ProductListing {
-        merchant_id: params.merchant_id,
-        product_id: params.product_id.clone(),
-        amount: params.amount,
-        wallet: params.wallet,
-        hash: params.hash,
-        state:ProductState::Listed
-    };
-    state.product_listings.insert(params.product_id,listing);
-    Ok(())
-}
+/// List of supported entrypoints by the `permit` function (CIS3 standard).
+/// Keep it in sync with the entrypoints dispatched by `permit`, apart from
+/// the old names `permit` still accepts for existing clients.
+pub const SUPPORTS_PERMIT_ENTRYPOINTS: [EntrypointName<'static>; 18] = [
+    EntrypointName::new_unchecked("list_product"),
+    EntrypointName::new_unchecked("update_product"),
+    EntrypointName::new_unchecked("set_product_metadata_url"),
+    EntrypointName::new_unchecked("cancel_product"),
+    EntrypointName::new_unchecked("place_order"),
+    EntrypointName::new_unchecked("confirm_order"),
+    EntrypointName::new_unchecked("mark_shipped"),
+    EntrypointName::new_unchecked("refund_order"),
+    EntrypointName::new_unchecked("reclaim_order"),
+    EntrypointName::new_unchecked("claim_payout"),
+    EntrypointName::new_unchecked("open_dispute"),
+    EntrypointName::new_unchecked("resolve_dispute"),
+    EntrypointName::new_unchecked("grantRole"),
+    EntrypointName::new_unchecked("revokeRole"),
+    EntrypointName::new_unchecked("set_fee"),
+    EntrypointName::new_unchecked("withdraw_fees"),
+    EntrypointName::new_unchecked("update_token_whitelist"),
+    EntrypointName::new_unchecked("update_merchant_accounts"),
+];
 
-/// Response type for the function `publicKeyOf`.
-#[derive(Debug, Serialize, SchemaType)]
-#[concordium(transparent)]
-pub struct PublicKeyOfQueryResponse(
-    #[concordium(size_length = 2)] pub Vec<Option<AccountPublicKeys>>,
-);
+/// Tag for the Refund event.
+pub const REFUND_EVENT_TAG: u8 = 0;
 
-impl From<Vec<Option<AccountPublicKeys>>> for PublicKeyOfQueryResponse {
-    fn from(results: concordium_std::Vec<Option<AccountPublicKeys>>) -> Self {
-        PublicKeyOfQueryResponse(results)
-    }
-}
+/// Tag for the DisputeOpened event.
+pub const DISPUTE_OPENED_EVENT_TAG: u8 = 1;
 
-/// The parameter type for the contract functions `publicKeyOf/noneOf`. A query
-/// for the public key/nonce of a given account.
-#[derive(Debug, Serialize, SchemaType)]
-#[concordium(transparent)]
-pub struct VecOfAccountAddresses {
-    /// List of queries.
-    #[concordium(size_length = 2)]
-    pub queries: Vec<AccountAddress>,
-}
+/// Tag for the DisputeResolved event.
+pub const DISPUTE_RESOLVED_EVENT_TAG: u8 = 2;
 
-/// Get the public keys of accounts. `None` is returned if the account does not
-/// exist on chain.
-///
-/// It rejects if:
-/// - It fails to parse the parameter.
-#[receive(
-    contract = "gonana_marketplace",
-    name = "publicKeyOf",
-    parameter = "VecOfAccountAddresses",
-    return_value = "PublicKeyOfQueryResponse",
-    error = "ContractError"
-)]
-fn contract_public_key_of(
+/// Tag for the GrantRole event.
+pub const GRANT_ROLE_EVENT_TAG: u8 = 3;
+
+/// Tag for the RevokeRole event.
+pub const REVOKE_ROLE_EVENT_TAG: u8 = 4;
+
+/// Tag for the ExcessRefund event.
+pub const EXCESS_REFUND_EVENT_TAG: u8 = 5;
+
+/// Tag for the ProductListed event.
+pub const PRODUCT_LISTED_EVENT_TAG: u8 = 6;
+
+/// Tag for the OrderPlaced event.
+pub const ORDER_PLACED_EVENT_TAG: u8 = 7;
+
+/// Tag for the OrderShipped event.
+pub const ORDER_SHIPPED_EVENT_TAG: u8 = 8;
+
+/// Tag for the OrderConfirmed event.
+pub const ORDER_CONFIRMED_EVENT_TAG: u8 = 9;
+
+/// Tag for the ProductCancelled event.
+pub const PRODUCT_CANCELLED_EVENT_TAG: u8 = 10;
+
+/// Tag for the FeeUpdated event.
+pub const FEE_UPDATED_EVENT_TAG: u8 = 11;
+
+/// Tag for the FeesWithdrawn event.
+pub const FEES_WITHDRAWN_EVENT_TAG: u8 = 12;
+
+/// Tag for the TokenWhitelistUpdated event.
+pub const TOKEN_WHITELIST_UPDATED_EVENT_TAG: u8 = 13;
+
+/// Tag for the Paused event.
+pub const PAUSED_EVENT_TAG: u8 = 14;
+
+/// Tag for the ProductUpdated event.
+pub const PRODUCT_UPDATED_EVENT_TAG: u8 = 15;
+
+/// Tag for the MerchantAccountUpdated event.
+pub const MERCHANT_ACCOUNT_UPDATED_EVENT_TAG: u8 = 16;
+
+/// Tag for the ProductMetadataUrl event.
+pub const PRODUCT_METADATA_URL_EVENT_TAG: u8 = 17;
+
+/// Tag for the BidPlaced event.
+pub const BID_PLACED_EVENT_TAG: u8 = 18;
+
+/// Tag for the BidRefunded event.
+pub const BID_REFUNDED_EVENT_TAG: u8 = 19;
+
+/// Tag for the CIS3 Nonce event.
+pub const NONCE_EVENT_TAG: u8 = u8::MAX - 5;
+
+/// Tagged events to be serialized for the event log.
+#[derive(Debug, Serial, Deserial, PartialEq, Eq)]
+#[concordium(repr(u8))]
+pub enum Event {
+    /// The event tracks an escrowed order whose amount was sent back to the
+    /// buyer.
+    #[concordium(tag = 0)]
+    Refund(RefundEvent),
+    /// The event tracks an escrowed order that was disputed.
+    #[concordium(tag = 1)]
+    DisputeOpened(DisputeOpenedEvent),
+    /// The event tracks how the arbiter split the escrow of a disputed order.
+    #[concordium(tag = 2)]
+    DisputeResolved(DisputeResolvedEvent),
+    /// The event tracks a role granted to an address.
+    #[concordium(tag = 3)]
+    GrantRole(RoleEvent),
+    /// The event tracks a role revoked from an address.
+    #[concordium(tag = 4)]
+    RevokeRole(RoleEvent),
+    /// The event tracks the amount paid above the price of an order that was
+    /// sent back to the sender.
+    #[concordium(tag = 5)]
+    ExcessRefund(ExcessRefundEvent),
+    /// The event tracks a product listed in the marketplace.
+    #[concordium(tag = 6)]
+    ProductListed(ProductListedEvent),
+    /// The event tracks an order placed for a product.
+    #[concordium(tag = 7)]
+    OrderPlaced(OrderPlacedEvent),
+    /// The event tracks an order the merchant shipped.
+    #[concordium(tag = 8)]
+    OrderShipped(OrderShippedEvent),
+    /// The event tracks an order whose escrow was paid to the merchant.
+    #[concordium(tag = 9)]
+    OrderConfirmed(OrderConfirmedEvent),
+    /// The event tracks a product withdrawn by its merchant.
+    #[concordium(tag = 10)]
+    ProductCancelled(ProductCancelledEvent),
+    /// The event tracks a new platform fee.
+    #[concordium(tag = 11)]
+    FeeUpdated(FeeUpdatedEvent),
+    /// The event tracks fees withdrawn from the treasury.
+    #[concordium(tag = 12)]
+    FeesWithdrawn(FeesWithdrawnEvent),
+    /// The event tracks a token added to or removed from the token whitelist.
+    #[concordium(tag = 13)]
+    TokenWhitelistUpdated(TokenWhitelistEvent),
+    /// The event tracks the marketplace being paused or unpaused.
+    #[concordium(tag = 14)]
+    Paused(SetPausedParams),
+    /// The event tracks a product whose price, wallet or hash was changed.
+    #[concordium(tag = 15)]
+    ProductUpdated(ProductUpdatedEvent),
+    /// The event tracks an account authorised for or revoked from a merchant.
+    #[concordium(tag = 16)]
+    MerchantAccountUpdated(MerchantAccountEvent),
+    /// The event tracks the metadata URL set for a product.
+    #[concordium(tag = 17)]
+    ProductMetadataUrl(ProductMetadataUrlEvent),
+    /// The event tracks a new highest bid in an auction.
+    #[concordium(tag = 18)]
+    BidPlaced(BidEvent),
+    /// The event tracks a bid sent back because it was outbid.
+    #[concordium(tag = 19)]
+    BidRefunded(BidEvent),
+    /// The event tracks the nonce used by the signer of the `PermitMessage`
+    /// whenever the `permit` function is invoked.
+    #[concordium(tag = 250)]
+    Nonce(NonceEvent),
+}
+
+/// The NonceEvent is logged when the `permit` function is invoked. The event
+/// tracks the nonce used by the signer of the `PermitMessage`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct NonceEvent {
+    /// Account that signed the `PermitMessage`.
+    pub account: AccountAddress,
+    /// The nonce that was used in the `PermitMessage`.
+    pub nonce:   u64,
+}
+
+/// The RefundEvent is logged when an open order is refunded, through
+/// `refund_order`, `reclaim_order` or when its product is cancelled.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RefundEvent {
+    /// The refunded order.
+    pub order_id:      OrderId,
+    /// Account that received the refund, `None` if nothing was escrowed.
+    pub buyer_address: Option<AccountAddress>,
+    /// The amount sent back to the buyer.
+    pub amount:        Amount,
+}
+
+/// The DisputeOpenedEvent is logged when the buyer or the merchant disputes an
+/// escrowed order.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct DisputeOpenedEvent {
+    /// The disputed order.
+    pub order_id:   OrderId,
+    /// Address that opened the dispute.
+    pub opened_by:  Address,
+    /// Short description of the problem with the order.
+    pub reason:     String,
+}
+
+/// The DisputeResolvedEvent is logged when the arbiter settles a disputed
+/// order.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct DisputeResolvedEvent {
+    /// The disputed order.
+    pub order_id:        OrderId,
+    /// Amount of the escrow sent back to the buyer.
+    pub buyer_amount:    Amount,
+    /// Amount of the escrow paid to the merchant.
+    pub merchant_amount: Amount,
+}
+
+/// The ExcessRefundEvent is logged when `place_order` receives more than the
+/// price of the product and sends the difference back.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ExcessRefundEvent {
+    /// The order that was overpaid.
+    pub order_id:   OrderId,
+    /// Account that received the excess.
+    pub to:         AccountAddress,
+    /// The amount sent back.
+    pub amount:     Amount,
+}
+
+/// The ProductListedEvent is logged when a product is listed.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ProductListedEvent {
+    /// The listed product.
+    pub product_id:  String,
+    /// The merchant of the product.
+    pub merchant_id: String,
+    /// Currency the product is priced in.
+    pub currency:    Currency,
+    /// Price of one unit of the product.
+    pub amount:      Amount,
+    /// Number of units available to order.
+    pub quantity:    u64,
+}
+
+/// The OrderPlacedEvent is logged when an order is placed, paid or through a
+/// sponsored `permit`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct OrderPlacedEvent {
+    /// The new order.
+    pub order_id:   OrderId,
+    /// The product the order was placed for.
+    pub product_id: String,
+    /// The buyer_id of the order.
+    pub buyer_id:   String,
+    /// Number of units ordered.
+    pub quantity:   u64,
+    /// Currency of the escrow.
+    pub currency:   Currency,
+    /// The amount held in escrow.
+    pub amount:     Amount,
+}
+
+/// The OrderShippedEvent is logged when the merchant marks an order as shipped.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct OrderShippedEvent {
+    /// The shipped order.
+    pub order_id:              OrderId,
+    /// Time after which the merchant can claim the payout.
+    pub confirmation_deadline: Timestamp,
+}
+
+/// The OrderConfirmedEvent is logged when the escrow of an order is paid to
+/// the merchant, through `confirm_order`, `permit` or `claim_payout`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct OrderConfirmedEvent {
+    /// The confirmed order.
+    pub order_id: OrderId,
+    /// Amount paid to the merchant.
+    pub payout:   Amount,
+    /// Platform fee kept in the treasury.
+    pub fee:      Amount,
+}
+
+/// The MerchantAccountEvent is logged when an account is authorised for or
+/// revoked from a merchant_id.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct MerchantAccountEvent {
+    /// The merchant.
+    pub merchant_id: String,
+    /// Whether the account was authorised or revoked.
+    pub update:      WhitelistUpdate,
+    /// The account.
+    pub account:     AccountAddress,
+}
+
+/// The ProductMetadataUrlEvent is logged when a product is listed with a
+/// metadata URL and whenever the merchant changes it.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ProductMetadataUrlEvent {
+    /// The product.
+    pub product_id:   String,
+    /// The new metadata URL, `None` if it was removed.
+    pub metadata_url: Option<MetadataUrl>,
+}
+
+/// The BidEvent is logged when a bid is placed in an auction and when it is
+/// refunded.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct BidEvent {
+    /// The auctioned product.
+    pub product_id: String,
+    /// Account that placed the bid.
+    pub bidder:     AccountAddress,
+    /// The amount of the bid.
+    pub amount:     Amount,
+}
+
+/// The ProductUpdatedEvent is logged when a merchant updates a listed product.
+/// It holds the values after the update.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ProductUpdatedEvent {
+    /// The updated product.
+    pub product_id: String,
+    /// Price of one unit of the product.
+    pub amount:     Amount,
+    /// Wallet address receiving the payouts.
+    pub wallet:     Option<AccountAddress>,
+    /// Hash of the `ProductMetadata` of the product.
+    pub hash:       Option<Sha256>,
+}
+
+/// The ProductCancelledEvent is logged when a merchant cancels a product.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ProductCancelledEvent {
+    /// The cancelled product.
+    pub product_id: String,
+}
+
+/// The FeeUpdatedEvent is logged when the admin sets the platform fee.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct FeeUpdatedEvent {
+    /// The new platform fee in basis points.
+    pub fee_bps: u16,
+}
+
+/// The FeesWithdrawnEvent is logged when the admin withdraws accrued fees.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct FeesWithdrawnEvent {
+    /// Currency of the withdrawn fees.
+    pub currency: Currency,
+    /// Account that received the fees.
+    pub receiver: AccountAddress,
+    /// The withdrawn amount.
+    pub amount:   Amount,
+}
+
+/// The TokenWhitelistEvent is logged when the admin updates the token
+/// whitelist.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct TokenWhitelistEvent {
+    /// Whether the token was added or removed.
+    pub update:   WhitelistUpdate,
+    /// The CIS-2 contract of the token.
+    pub contract: ContractAddress,
+    /// The id of the token in `contract`.
+    pub token_id: ContractTokenId,
+}
+
+/// The RoleEvent is logged when a role is granted to or revoked from an
+/// address.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct RoleEvent {
+    /// Address the role was granted to or revoked from.
+    pub address: Address,
+    /// The role.
+    pub role:    Role,
+}
+
+
+// Implementing a custom schemaType to the `Event` combining all CIS2/CIS3
+// events.
+impl schema::SchemaType for Event {
+    fn get_type() -> schema::Type {
+        let mut event_map = collections::BTreeMap::new();
+        event_map.insert(
+            REFUND_EVENT_TAG,
+            (
+                "Refund".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("buyer_address"), Option::<AccountAddress>::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            DISPUTE_OPENED_EVENT_TAG,
+            (
+                "DisputeOpened".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("opened_by"), Address::get_type()),
+                    (String::from("reason"), String::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            DISPUTE_RESOLVED_EVENT_TAG,
+            (
+                "DisputeResolved".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("buyer_amount"), Amount::get_type()),
+                    (String::from("merchant_amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            GRANT_ROLE_EVENT_TAG,
+            (
+                "GrantRole".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("address"), Address::get_type()),
+                    (String::from("role"), Role::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            REVOKE_ROLE_EVENT_TAG,
+            (
+                "RevokeRole".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("address"), Address::get_type()),
+                    (String::from("role"), Role::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            EXCESS_REFUND_EVENT_TAG,
+            (
+                "ExcessRefund".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("to"), AccountAddress::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            PRODUCT_LISTED_EVENT_TAG,
+            (
+                "ProductListed".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("merchant_id"), String::get_type()),
+                    (String::from("currency"), Currency::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                    (String::from("quantity"), u64::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            ORDER_PLACED_EVENT_TAG,
+            (
+                "OrderPlaced".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("buyer_id"), String::get_type()),
+                    (String::from("quantity"), u64::get_type()),
+                    (String::from("currency"), Currency::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            ORDER_SHIPPED_EVENT_TAG,
+            (
+                "OrderShipped".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("confirmation_deadline"), Timestamp::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            ORDER_CONFIRMED_EVENT_TAG,
+            (
+                "OrderConfirmed".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("order_id"), OrderId::get_type()),
+                    (String::from("payout"), Amount::get_type()),
+                    (String::from("fee"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            PRODUCT_CANCELLED_EVENT_TAG,
+            (
+                "ProductCancelled".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            FEE_UPDATED_EVENT_TAG,
+            (
+                "FeeUpdated".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("fee_bps"), u16::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            FEES_WITHDRAWN_EVENT_TAG,
+            (
+                "FeesWithdrawn".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("currency"), Currency::get_type()),
+                    (String::from("receiver"), AccountAddress::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            TOKEN_WHITELIST_UPDATED_EVENT_TAG,
+            (
+                "TokenWhitelistUpdated".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("update"), WhitelistUpdate::get_type()),
+                    (String::from("contract"), ContractAddress::get_type()),
+                    (String::from("token_id"), ContractTokenId::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            PAUSED_EVENT_TAG,
+            (
+                "Paused".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("paused"), bool::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            PRODUCT_UPDATED_EVENT_TAG,
+            (
+                "ProductUpdated".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                    (String::from("wallet"), Option::<AccountAddress>::get_type()),
+                    (String::from("hash"), Option::<Sha256>::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            MERCHANT_ACCOUNT_UPDATED_EVENT_TAG,
+            (
+                "MerchantAccountUpdated".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("merchant_id"), String::get_type()),
+                    (String::from("update"), WhitelistUpdate::get_type()),
+                    (String::from("account"), AccountAddress::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            PRODUCT_METADATA_URL_EVENT_TAG,
+            (
+                "ProductMetadataUrl".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("metadata_url"), Option::<MetadataUrl>::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            BID_PLACED_EVENT_TAG,
+            (
+                "BidPlaced".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("bidder"), AccountAddress::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            BID_REFUNDED_EVENT_TAG,
+            (
+                "BidRefunded".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("product_id"), String::get_type()),
+                    (String::from("bidder"), AccountAddress::get_type()),
+                    (String::from("amount"), Amount::get_type()),
+                ]),
+            ),
+        );
+        event_map.insert(
+            NONCE_EVENT_TAG,
+            (
+                "Nonce".to_string(),
+                schema::Fields::Named(vec![
+                    (String::from("account"), AccountAddress::get_type()),
+                    (String::from("nonce"), u64::get_type()),
+                ]),
+            ),
+        );
+        schema::Type::TaggedEnum(event_map)
+    }
+}
+
+
+/// Part of the parameter type for the contract function `permit`.
+/// Specifies the message that is signed.
+#[derive(SchemaType, Serialize)]
+pub struct PermitMessage {
+    /// The contract_address that the signature is intended for.
+    pub contract_address: ContractAddress,
+    /// A nonce to prevent replay attacks.
+    pub nonce:            u64,
+    /// A timestamp to make signatures expire.
+    pub timestamp:        Timestamp,
+    /// The entry_point that the signature is intended for.
+    pub entry_point:      OwnedEntrypointName,
+    /// The serialized payload that should be forwarded to either the `transfer`
+    /// or the `updateOperator` function.
+    #[concordium(size_length = 2)]
+    pub payload:          Vec<u8>,
+}
+
+/// The parameter type for the contract function `permit`.
+/// Takes a signature, the signer, and the message that was signed.
+#[derive(Serialize, SchemaType)]
+pub struct PermitParam {
+    /// Signature/s. The CIS3 standard supports multi-sig accounts.
+    pub signature: AccountSignatures,
+    /// Account that created the above signature.
+    pub signer:    AccountAddress,
+    /// Message that was signed.
+    pub message:   PermitMessage,
+}
+
+#[derive(Serialize)]
+pub struct PermitParamPartial {
+    /// Signature/s. The CIS3 standard supports multi-sig accounts.
+    signature: AccountSignatures,
+    /// Account that created the above signature.
+    signer:    AccountAddress,
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+/// Version of the layout of `State`. Bump it whenever the layout changes and
+/// teach `migrate` to convert the previous layout.
+pub const STATE_VERSION: u32 = 1;
+
+/// Smart contract state
+#[derive(Serial, DeserialWithState)]
+#[concordium(state_parameter = "S")]
+pub struct State<S = StateApi>  {
+    /// Version of the layout, always `STATE_VERSION` once `migrate` ran. It
+    /// is the first field so it can be read without knowing the layout.
+    version: u32,
+    /// Contract is paused if `paused = true` and unpaused if `paused = false`.
+    /// While paused no products can be listed and no orders placed, but
+    /// escrowed orders can still be settled, refunded and disputed.
+    paused: bool,
+    /// The roles granted to each address.
+    roles: StateSet<(Address, Role), S>,
+    /// Platform fee in basis points of every settled amount.
+    fee_bps: u16,
+    /// Fees kept from settlements that have not been withdrawn yet, per
+    /// currency.
+    treasury: StateMap<Currency, Amount, S>,
+    /// The CIS-2 tokens products can be priced in.
+    token_whitelist: StateSet<(ContractAddress, ContractTokenId), S>,
+    pub product_listings: StateMap<String,ProductListing, S>,
+    /// The product_id of each listing by the order it was listed in, so the
+    /// listings can be paged without walking `product_listings`.
+    listing_ids: StateMap<u64, String, S>,
+    /// The position of the next listing in `listing_ids`.
+    next_listing_seq: u64,
+    pub orders: StateMap<OrderId,Order,S>,
+    /// The id assigned to the next order.
+    next_order_id: OrderId,
+    /// The ids of all orders placed for each product.
+    product_orders: StateMap<String, StateSet<OrderId, S>, S>,
+    /// The ids of the orders of each buyer_id by the order they were placed
+    /// in, so the orders of a buyer can be paged.
+    buyer_orders: StateMap<(String, u64), OrderId, S>,
+    /// The number of orders placed by each buyer_id.
+    buyer_order_count: StateMap<String, u64, S>,
+    /// The ids of the active products of each merchant_id. A product stays
+    /// active until it is cancelled, or sold out with all orders settled.
+    merchant_products: StateMap<String, StateSet<String, S>, S>,
+    /// The accounts authorised to act for each merchant_id.
+    merchants: StateMap<String, StateSet<AccountAddress, S>, S>,
+    nonces_registry:  StateMap<AccountAddress, u64, S>,
+    /// Map with contract addresses providing implementations of additional
+    /// standards.
+    implementors: StateMap<StandardIdentifierOwned, Vec<ContractAddress>, S>,
+}
+
+impl State {
+    /// Check if an address has been granted a role.
+    fn has_role(&self, address: &Address, role: Role) -> bool {
+        self.roles.contains(&(*address, role))
+    }
+
+    /// Grant a role to an address. Succeeds even if the address already has
+    /// the role.
+    fn grant_role(&mut self, address: Address, role: Role) {
+        self.roles.insert((address, role));
+    }
+
+    /// Revoke a role from an address. Succeeds even if the address does not
+    /// have the role.
+    fn revoke_role(&mut self, address: &Address, role: Role) {
+        self.roles.remove(&(*address, role));
+    }
+
+    /// Check whether products can be priced in a currency: CCD always, CIS-2
+    /// tokens only while they are whitelisted.
+    fn accepts(&self, currency: &Currency) -> bool {
+        match currency {
+            Currency::Ccd => true,
+            Currency::Cis2 {
+                contract,
+                token_id,
+            } => self.token_whitelist.contains(&(*contract, token_id.clone())),
+        }
+    }
+
+    /// Reserve `params.quantity` units of a listed product and store a new
+    /// order escrowing `amount` in the currency of the listing. Logs an
+    /// `OrderPlaced` event and returns the id of the order.
+    fn create_order(
+        &mut self,
+        params: PlaceOrderParameter,
+        amount: Amount,
+        now: Timestamp,
+        state_builder: &mut StateBuilder,
+        logger: &mut impl HasLogger,
+    ) -> Result<OrderId, MarketplaceError> {
+        let currency = self
+            .product_listings
+            .get(&params.product_id)
+            .ok_or(MarketplaceError::ProductNotFound)?
+            .currency
+            .clone();
+        ensure!(self.accepts(&currency), MarketplaceError::UnsupportedToken);
+
+        let mut product =
+            self.product_listings.get_mut(&params.product_id).ok_or(MarketplaceError::ProductNotFound)?;
+        // Ensure that the product is in a valid state for placing an order
+        ensure!(product.state == ProductState::Listed, MarketplaceError::InvalidProductState);
+        product.reserve(params.quantity)?;
+        drop(product);
+
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.product_orders
+            .entry(params.product_id.clone())
+            .or_insert_with(|| state_builder.new_set())
+            .insert(id);
+        let mut count = self.buyer_order_count.entry(params.buyer_id.clone()).or_insert(0);
+        self.buyer_orders.insert((params.buyer_id.clone(), *count), id);
+        *count += 1;
+        drop(count);
+        logger.log(&Event::OrderPlaced(OrderPlacedEvent {
+            order_id: id,
+            product_id: params.product_id.clone(),
+            buyer_id: params.buyer_id.clone(),
+            quantity: params.quantity,
+            currency: currency.clone(),
+            amount,
+        }))?;
+        self.orders.insert(id, Order::new(id, params, currency, amount, self.fee_bps, now));
+        Ok(id)
+    }
+
+    /// Check if an address is an account authorised to act for a merchant_id.
+    fn is_merchant_account(&self, merchant_id: &String, address: &Address) -> bool {
+        match address {
+            Address::Account(account) => {
+                self.merchants.get(merchant_id).is_some_and(|accounts| accounts.contains(account))
+            }
+            Address::Contract(_) => false,
+        }
+    }
+
+    /// Ensure that an address is an account authorised to act for a
+    /// merchant_id.
+    fn ensure_merchant(&self, merchant_id: &String, address: &Address) -> Result<(), MarketplaceError> {
+        ensure!(self.is_merchant_account(merchant_id, address), MarketplaceError::UnauthorizedMerchant);
+        Ok(())
+    }
+
+    /// Remove a product from the active products of its merchant.
+    fn unindex_product(&mut self, merchant_id: &String, product_id: &String) {
+        if let Some(mut products) = self.merchant_products.get_mut(merchant_id) {
+            products.remove(product_id);
+        }
+    }
+
+    /// Remove a product from the active products of its merchant once it is
+    /// sold out and none of its orders are open anymore.
+    fn unindex_if_settled(&mut self, product_id: &String) {
+        let merchant_id = match self.product_listings.get(product_id) {
+            Some(listing) if listing.state == ProductState::SoldOut => listing.merchant_id.clone(),
+            _ => return,
+        };
+        let has_open_orders = self.product_orders.get(product_id).is_some_and(|order_ids| {
+            order_ids.iter().any(|id| self.orders.get(&id).is_some_and(|order| order.is_open()))
+        });
+        if !has_open_orders {
+            self.unindex_product(&merchant_id, product_id);
+        }
+    }
+
+    /// Deduct the platform fee at `fee_bps` from a settled amount and keep it
+    /// in the treasury. Returns the part of the amount owed to the merchant.
+    fn deduct_fee(&mut self, currency: Currency, amount: Amount, fee_bps: u16) -> Amount {
+        let fee = fee_of(amount, fee_bps);
+        self.keep_fee(currency, fee);
+        amount - fee
+    }
+
+    /// Keep a deducted platform fee in the treasury.
+    fn keep_fee(&mut self, currency: Currency, fee: Amount) {
+        *self.treasury.entry(currency).or_insert(Amount::zero()) += fee;
+    }
+
+    /// Check if state contains any implementors for a given standard.
+    fn have_implementors(&self, std_id: &StandardIdentifierOwned) -> SupportResult {
+        if let Some(addresses) = self.implementors.get(std_id) {
+            SupportResult::SupportBy(addresses.to_vec())
+        } else {
+            SupportResult::NoSupport
+        }
+    }
+
+    /// Set implementors for a given standard.
+    fn set_implementors(
+        &mut self,
+        std_id: StandardIdentifierOwned,
+        implementors: Vec<ContractAddress>,
+    ) {
+        self.implementors.insert(std_id, implementors);
+    }
+}
+
+// Init function to initialize the marketplace state
+// The instantiator of the contract is granted the `Admin` role.
+#[init(contract = "gonana_marketplace", enable_logger, event = "Event")]
+fn init(
+    ctx: &InitContext,
+    state_builder: &mut StateBuilder,
+    logger: &mut impl HasLogger,
+) -> InitResult<State>{
+    let admin = Address::Account(ctx.init_origin());
+    let mut state = State { 
+            version: STATE_VERSION,
+            paused: false,
+            roles: state_builder.new_set(),
+            fee_bps: 0,
+            treasury: state_builder.new_map(),
+            token_whitelist: state_builder.new_set(),
+            product_listings: state_builder.new_map(),
+            listing_ids: state_builder.new_map(),
+            next_listing_seq: 0,
+            orders: state_builder.new_map(),
+            next_order_id: 0,
+            product_orders: state_builder.new_map(),
+            buyer_orders: state_builder.new_map(),
+            buyer_order_count: state_builder.new_map(),
+            merchant_products: state_builder.new_map(),
+            merchants: state_builder.new_map(),
+            nonces_registry:  state_builder.new_map(),
+            implementors: state_builder.new_map(),
+     };
+    state.grant_role(admin, Role::Admin);
+
+    // Log event for the new admin.
+    logger.log(&Event::GrantRole(RoleEvent {
+        address: admin,
+        role:    Role::Admin,
+    }))?;
+    Ok(state)
+}
+
+// internal list function shared by `list_product` and the permit message
+fn internal_list_product(
+    host: &mut Host<State>,
+    params: ListProductParameter,
+    sender: Address,
+    now: Timestamp,
+    logger: &mut impl HasLogger,
+) -> Result<(),MarketplaceError>{
+    // Check if the price is 0
+    ensure!(params.amount > Amount::zero(), MarketplaceError::InvalidPrice);
+    // Check that there is something to sell
+    ensure!(params.quantity > 0, MarketplaceError::InvalidQuantity);
+    // Check if the product name is empty
+    ensure!(!params.product_id.is_empty(), MarketplaceError::ParseParams);
+    // Check that there is a wallet to pay the merchant out to
+    ensure!(params.wallet.is_some(), MarketplaceError::MissingWallet);
+
+    let (state, builder) = host.state_and_builder();
+    state.ensure_merchant(&params.merchant_id, &sender)?;
+    // Ensure product has not been listed before, re-listing would orphan its
+    // orders.
+    ensure!(state.product_listings.get(&params.product_id).is_none(), MarketplaceError::ProductAlreadyExists);
+    ensure!(state.accepts(&params.currency), MarketplaceError::UnsupportedToken);
+    // Bids are escrowed as CCD attached to `bid`.
+    if let Some(terms) = &params.auction {
+        ensure!(params.currency == Currency::Ccd, MarketplaceError::WrongCurrency);
+        ensure!(terms.min_increment > Amount::zero(), MarketplaceError::InvalidPrice);
+        ensure!(terms.end > now, MarketplaceError::AuctionEnded);
+        // The reserve price of all units must be representable, else no bid
+        // could ever be accepted.
+        ensure!(params.amount.micro_ccd.checked_mul(params.quantity).is_some(), MarketplaceError::InvalidPrice);
+    }
+    
+    logger.log(&Event::ProductListed(ProductListedEvent {
+        product_id: params.product_id.clone(),
+        merchant_id: params.merchant_id.clone(),
+        currency: params.currency.clone(),
+        amount: params.amount,
+        quantity: params.quantity,
+    }))?;
+    if params.metadata_url.is_some() {
+        logger.log(&Event::ProductMetadataUrl(ProductMetadataUrlEvent {
+            product_id: params.product_id.clone(),
+            metadata_url: params.metadata_url.clone(),
+        }))?;
+    }
+
+    let listing = ProductListing {
+        merchant_id: params.merchant_id,
+        product_id: params.product_id.clone(),
+        amount: params.amount,
+        currency: params.currency,
+        quantity: params.quantity,
+        unit: params.unit,
+        wallet: params.wallet,
+        hash: params.hash,
+        metadata_url: params.metadata_url,
+        state:ProductState::Listed,
+        auction: params.auction.map(|terms| Auction {
+            min_increment: terms.min_increment,
+            end: terms.end,
+            highest_bid: None,
+        }),
+    };
+    state
+        .merchant_products
+        .entry(listing.merchant_id.clone())
+        .or_insert_with(|| builder.new_set())
+        .insert(params.product_id.clone());
+    let seq = state.next_listing_seq;
+    state.next_listing_seq += 1;
+    state.listing_ids.insert(seq, params.product_id.clone());
+    state.product_listings.insert(params.product_id,listing);
+    Ok(())
+}
+
+/// Response type for the function `publicKeyOf`.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct PublicKeyOfQueryResponse(
+    #[concordium(size_length = 2)] pub Vec<Option<AccountPublicKeys>>,
+);
+
+impl From<Vec<Option<AccountPublicKeys>>> for PublicKeyOfQueryResponse {
+    fn from(results: concordium_std::Vec<Option<AccountPublicKeys>>) -> Self {
+        PublicKeyOfQueryResponse(results)
+    }
+}
+
+/// The parameter type for the contract functions `publicKeyOf/noneOf`. A query
+/// for the public key/nonce of a given account.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct VecOfAccountAddresses {
+    /// List of queries.
+    #[concordium(size_length = 2)]
+    pub queries: Vec<AccountAddress>,
+}
+
+/// Get the public keys of accounts. `None` is returned if the account does not
+/// exist on chain.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "publicKeyOf",
+    parameter = "VecOfAccountAddresses",
+    return_value = "PublicKeyOfQueryResponse",
+    error = "MarketplaceError"
+)]
+fn contract_public_key_of(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+) -> Result<PublicKeyOfQueryResponse,MarketplaceError> {
+    // Parse the parameter.
+    let params: VecOfAccountAddresses = ctx.parameter_cursor().get()?;
+    // Build the response.
+    let mut response: Vec<Option<AccountPublicKeys>> = Vec::with_capacity(params.queries.len());
+    for account in params.queries {
+        // Query the public_key.
+        let public_keys = host.account_public_keys(account).ok();
+
+        response.push(public_keys);
+    }
+    let result = PublicKeyOfQueryResponse::from(response);
+    Ok(result)
+}
+
+/// Response type for the function `nonceOf`.
+#[derive(Debug, Serialize, SchemaType)]
+#[concordium(transparent)]
+pub struct NonceOfQueryResponse(#[concordium(size_length = 2)] pub Vec<u64>);
+
+impl From<Vec<u64>> for NonceOfQueryResponse {
+    fn from(results: concordium_std::Vec<u64>) -> Self { NonceOfQueryResponse(results) }
+}
+
+/// Get the nonces of accounts.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "nonceOf",
+    parameter = "VecOfAccountAddresses",
+    return_value = "NonceOfQueryResponse",
+    error = "MarketplaceError"
+)]
+fn contract_nonce_of(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+) -> Result<NonceOfQueryResponse,MarketplaceError> {
+    // Parse the parameter.
+    let params: VecOfAccountAddresses = ctx.parameter_cursor().get()?;
+    // Build the response.
+    let mut response: Vec<u64> = Vec::with_capacity(params.queries.len());
+    for account in params.queries {
+        // Query the next nonce.
+        let nonce = host.state().nonces_registry.get(&account).map(|nonce| *nonce).unwrap_or(0);
+
+        response.push(nonce);
+    }
+    Ok(NonceOfQueryResponse::from(response))
+}
+
+
+
+/// Helper function to calculate the `message_hash`. This is the hash the
+/// signer signs in the wallet and the one `permit` checks the signature
+/// against.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "viewMessageHash",
+    parameter = "PermitParam",
+    return_value = "[u8;32]",
+    error = "MarketplaceError",
+    crypto_primitives
+)]
+fn contract_view_message_hash(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> Result<[u8; 32],MarketplaceError> {
+    // Parse the parameter.
+    let mut cursor = ctx.parameter_cursor();
+    // The input parameter is `PermitParam` but we only read the initial part of it
+    // with `PermitParamPartial`. I.e. we read the `signature` and the
+    // `signer`, but not the `message` here.
+    let param: PermitParamPartial = cursor.get()?;
+
+    // The input parameter is `PermitParam` but we have only read the initial part
+    // of it with `PermitParamPartial` so far. We read in the `message` now.
+    // `(cursor.size() - cursor.cursor_position()` is the length of the message in
+    // bytes.
+    let mut message_bytes = vec![0; (cursor.size() - cursor.cursor_position()) as usize];
+
+    cursor.read_exact(&mut message_bytes)?;
+
+    // The message signed in the Concordium browser wallet is prepended with the
+    // `account` address and 8 zero bytes. Accounts in the Concordium browser wallet
+    // can either sign a regular transaction (in that case the prepend is
+    // `account` address and the nonce of the account which is by design >= 1)
+    // or sign a message (in that case the prepend is `account` address and 8 zero
+    // bytes). Hence, the 8 zero bytes ensure that the user does not accidentally
+    // sign a transaction. The account nonce is of type u64 (8 bytes).
+    let mut msg_prepend = [0; 32 + 8];
+    // Prepend the `account` address of the signer.
+    msg_prepend[0..32].copy_from_slice(param.signer.as_ref());
+    // Prepend 8 zero bytes.
+    msg_prepend[32..40].copy_from_slice(&[0u8; 8]);
+    // Calculate the message hash.
+    let message_hash =
+        crypto_primitives.hash_sha2_256(&[&msg_prepend[0..40], &message_bytes].concat()).0;
+
+    Ok(message_hash)
+}
+
+
+/// Executes a message on behalf of the signer, so that a sponsor can pay the
+/// transaction fees. The signature is checked against the message hash as
+/// computed by `viewMessageHash`. The message is dispatched to the same
+/// internal function as the entrypoint it names, with the signer as sender.
+///
+/// The entrypoints that can be permitted are listed in
+/// `SUPPORTS_PERMIT_ENTRYPOINTS`. These are not:
+/// - `bid`, as a sponsor cannot attach CCD on behalf of the signer.
+/// - The batch entrypoints `list_products` and `confirm_orders`.
+/// - `settle_auction`, which anyone can call.
+/// - `setPaused`, `setImplementors`, `upgrade`, `migrate` and the hook
+///   `onReceivingCIS2`.
+///
+/// A permitted `place_order` pulls the price from the signer, who must have
+/// made the marketplace an operator on the token contract the product is
+/// priced in; products priced in CCD cannot be ordered by permit. The old
+/// names `internal_list_product` and `cancel_order` are still dispatched for
+/// existing clients, but are not advertised by `supportsPermit`.
+///
+/// It rejects if:
+/// - The contract is paused.
+/// - It fails to parse the parameter or the payload of the message.
+/// - The nonce is wrong.
+/// - The message was intended for a different contract.
+/// - The message is expired.
+/// - The signature is invalid.
+/// - The entrypoint is not supported.
+/// - The permitted function rejects.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "permit",
+    parameter = "PermitParam",
+    error = "MarketplaceError",
+    crypto_primitives,
+    mutable,
+    enable_logger
+)]
+fn contract_permit(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> Result<(),MarketplaceError> {
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
+
+    // Parse the parameter.
+    let param: PermitParam = ctx.parameter_cursor().get()?;
+
+    // Update the nonce.
+    let mut entry = host.state_mut().nonces_registry.entry(param.signer).or_insert_with(|| 0);
+
+    // Get the current nonce.
+    let nonce = *entry;
+    // Bump nonce.
+    *entry += 1;
+    drop(entry);
+
+    let message = param.message;
+
+    // Check the nonce to prevent replay attacks.
+    ensure_eq!(message.nonce, nonce, MarketplaceError::NonceAlreadyUsed);
+
+    // Check that the signature was intended for this contract.
+    ensure_eq!(
+        message.contract_address,
+        ctx.self_address(),
+        MarketplaceError::WrongContract
+    );
+    // Check signature is not expired.
+    ensure!(message.timestamp > ctx.metadata().slot_time(), MarketplaceError::Expired);
+
+    // Check the signature against the hash of the prepended message.
+    let message_hash = contract_view_message_hash(ctx, host, crypto_primitives)?;
+    let valid_signature = host
+        .check_account_signature(param.signer, &param.signature, &message_hash)
+        .map_err(|_| MarketplaceError::WrongSignature)?;
+    ensure!(valid_signature, MarketplaceError::WrongSignature);
+
+    // Log the nonce event.
+    logger.log(&Event::Nonce(NonceEvent {
+        account: param.signer,
+        nonce,
+    }))?;
+
+    // Execute the function call on behalf of the signer.
+    let sender = Address::Account(param.signer);
+    let entry_point: &str = message.entry_point.as_entrypoint_name().into();
+    match entry_point {
+        "list_product" | "internal_list_product" => {
+            internal_list_product(host, from_bytes(&message.payload)?, sender, ctx.metadata().slot_time(), logger)
+        }
+        "update_product" => internal_update_product(host, from_bytes(&message.payload)?, sender, logger),
+        "set_product_metadata_url" => {
+            internal_set_product_metadata_url(host, from_bytes(&message.payload)?, sender, logger)
+        }
+        "cancel_product" | "cancel_order" => {
+            internal_cancel_product(ctx, host, from_bytes(&message.payload)?, sender, logger)
+        }
+        "place_order" => pull_order_payment(ctx, host, from_bytes(&message.payload)?, param.signer),
+        "confirm_order" => internal_confirm_order(ctx, host, from_bytes(&message.payload)?, sender, logger),
+        "mark_shipped" => internal_mark_shipped(ctx, host, from_bytes(&message.payload)?, sender, logger),
+        "refund_order" => internal_refund_order(ctx, host, from_bytes(&message.payload)?, sender, logger),
+        "reclaim_order" => internal_reclaim_order(ctx, host, from_bytes(&message.payload)?, sender, logger),
+        "claim_payout" => internal_claim_payout(ctx, host, from_bytes(&message.payload)?, logger),
+        "open_dispute" => internal_open_dispute(host, from_bytes(&message.payload)?, sender, logger),
+        "resolve_dispute" => {
+            internal_resolve_dispute(ctx, host, from_bytes(&message.payload)?, sender, logger)
+        }
+        "grantRole" => internal_grant_role(host, from_bytes(&message.payload)?, sender, logger),
+        "revokeRole" => internal_revoke_role(host, from_bytes(&message.payload)?, sender, logger),
+        "set_fee" => internal_set_fee(host, from_bytes(&message.payload)?, sender, logger),
+        "withdraw_fees" => internal_withdraw_fees(ctx, host, from_bytes(&message.payload)?, sender, logger),
+        "update_token_whitelist" => {
+            internal_update_token_whitelist(host, from_bytes(&message.payload)?, sender, logger)
+        }
+        "update_merchant_accounts" => {
+            internal_update_merchant_accounts(host, from_bytes(&message.payload)?, sender, logger)
+        }
+        _ => Err(MarketplaceError::WrongFunctionCall),
+    }
+}
+
+/// Sends the escrow of an open order back to the buyer and returns the
+/// ordered units to the stock of the listing. The listing of an auction is
+/// cancelled instead, logging a `ProductCancelled` event. The order is kept
+/// with the status `Refunded`. Logs a `Refund` event.
+///
+/// It rejects if:
+/// - There is no such order or it is not open.
+/// - The order holds funds but has no `buyer_address` to refund to.
+/// - The transfer to the buyer fails.
+fn refund_escrow(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    order_id: OrderId,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let state_mut = host.state_mut();
+    let mut order = state_mut.orders.get_mut(&order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(order.is_open(), MarketplaceError::InvalidOrderStatus);
+    order.status = OrderStatus::Refunded;
+    let (product_id, quantity, currency, amount, buyer_address) =
+        (order.product_id.clone(), order.quantity, order.currency.clone(), order.amount, order.buyer_address);
+    drop(order);
+
+    // The order of an auction holds all units of an auction that has ended,
+    // so its listing is cancelled instead of being listed again.
+    let cancelled = match state_mut.product_listings.get_mut(&product_id) {
+        Some(mut listing) if listing.auction.is_some() => {
+            let merchant_id =
+                (listing.state != ProductState::Cancelled).then(|| listing.merchant_id.clone());
+            listing.state = ProductState::Cancelled;
+            merchant_id
+        }
+        Some(mut listing) => {
+            listing.restock(quantity);
+            None
+        }
+        None => None,
+    };
+    if let Some(merchant_id) = cancelled {
+        state_mut.unindex_product(&merchant_id, &product_id);
+        logger.log(&Event::ProductCancelled(ProductCancelledEvent {
+            product_id: product_id.clone(),
+        }))?;
+    }
+
+    if amount > Amount::zero() {
+        let buyer_address = buyer_address.ok_or(MarketplaceError::MissingBuyerAddress)?;
+        transfer_funds(ctx, host, currency, buyer_address, amount)?;
+    }
+    logger.log(&Event::Refund(RefundEvent {
+        order_id,
+        buyer_address,
+        amount,
+    }))?;
+    Ok(())
+}
+
+// internal cancel function shared by `cancel_product` and the permit message
+fn internal_cancel_product(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    params: CancelProductParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let state_mut = host.state_mut();
+    state_mut.ensure_merchant(&params.merchant_id, &sender)?;
+    let mut listing =
+        state_mut.product_listings.get_mut(&params.product_id).ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(params.merchant_id == listing.merchant_id, MarketplaceError::UnauthorizedMerchant);
+    // Check if the product is in a cancellable state
+    ensure!(listing.state != ProductState::Cancelled, MarketplaceError::InvalidProductState);
+    // The merchant cannot back out of an auction someone bid in
+    ensure!(
+        listing.auction.as_ref().is_none_or(|auction| auction.highest_bid.is_none()),
+        MarketplaceError::AuctionHasBids
+    );
+    listing.state = ProductState::Cancelled;
+    drop(listing);
+    state_mut.unindex_product(&params.merchant_id, &params.product_id);
+    logger.log(&Event::ProductCancelled(ProductCancelledEvent {
+        product_id: params.product_id.clone(),
+    }))?;
+
+    // Send the escrow of orders that were not shipped yet back to the buyers.
+    // Orders holding funds without a `buyer_address` to refund are left
+    // escrowed for a dispute, so they do not block the cancellation.
+    let unshipped: Vec<OrderId> = match state_mut.product_orders.get(&params.product_id) {
+        Some(order_ids) => order_ids
+            .iter()
+            .map(|id| *id)
+            .filter(|id| {
+                state_mut
+                    .orders
+                    .get(id)
+                    .map(|order| {
+                        order.status == OrderStatus::Escrowed
+                            && (order.buyer_address.is_some() || order.amount == Amount::zero())
+                    })
+                    .unwrap_or(false)
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    for order_id in unshipped {
+        refund_escrow(ctx, host, order_id, logger)?;
+    }
+    Ok(())
+}
+
+
+
+/// Function to list a product in the marketplace. Only accounts registered
+/// for the `merchant_id` can list products for it.
+#[receive(contract = "gonana_marketplace", name = "list_product", parameter = "ListProductParameter", mutable, enable_logger)]
+fn list_product(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError>{
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
+
+    let parameter: ListProductParameter = ctx.parameter_cursor().get()?;
+    internal_list_product(host, parameter, ctx.sender(), ctx.metadata().slot_time(), logger)
+}
+
+/// Function to list several products in one transaction. Returns the outcome
+/// of every product, in the order given. A product is only stored once all
+/// its checks passed, so in `BestEffort` mode a failing product leaves no
+/// trace.
+///
+/// It rejects if:
+/// - The contract is paused.
+/// - It fails to parse the parameter.
+/// - In `AllOrNothing` mode, any product cannot be listed.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "list_products",
+    parameter = "ListProductsParameter",
+    return_value = "Vec<BatchItemResult>",
+    mutable,
+    enable_logger
+)]
+fn list_products(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<Vec<BatchItemResult>, MarketplaceError> {
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
+
+    let parameter: ListProductsParameter = ctx.parameter_cursor().get()?;
+    let sender = ctx.sender();
+    let now = ctx.metadata().slot_time();
+
+    let mut results = Vec::with_capacity(parameter.products.len());
+    for product in parameter.products {
+        match internal_list_product(host, product, sender, now, logger) {
+            Ok(()) => results.push(BatchItemResult::Ok),
+            Err(error) if parameter.mode == BatchMode::BestEffort => {
+                results.push(BatchItemResult::Failed(error))
+            }
+            Err(error) => bail!(error),
+        }
+    }
+    Ok(results)
+}
+
+/// Function for the merchant to change the price, wallet or hash of a product.
+/// Orders already placed keep the price they were placed for.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - Sender is not an account of the merchant.
+/// - The product is not found.
+/// - The `merchant_id` does not match the one of the listing.
+/// - The product is not `Listed`.
+/// - The new price is zero.
+#[receive(contract = "gonana_marketplace", name = "update_product", parameter = "UpdateProductParameter", mutable, enable_logger)]
+fn update_product(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: UpdateProductParameter = ctx.parameter_cursor().get()?;
+    internal_update_product(host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `update_product` and the permit message
+fn internal_update_product(
+    host: &mut Host<State>,
+    parameter: UpdateProductParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    host.state().ensure_merchant(&parameter.merchant_id, &sender)?;
+
+    let mut listing = host
+        .state_mut()
+        .product_listings
+        .get_mut(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(parameter.merchant_id == listing.merchant_id, MarketplaceError::UnauthorizedMerchant);
+    ensure!(listing.state == ProductState::Listed, MarketplaceError::InvalidProductState);
+
+    if let Some(amount) = parameter.amount {
+        ensure!(amount > Amount::zero(), MarketplaceError::InvalidPrice);
+        // The reserve price of an auction is fixed once someone bid.
+        ensure!(
+            listing.auction.as_ref().is_none_or(|auction| auction.highest_bid.is_none()),
+            MarketplaceError::InvalidProductState
+        );
+        listing.amount = amount;
+    }
+    if let Some(wallet) = parameter.wallet {
+        listing.wallet = Some(wallet);
+    }
+    if let Some(hash) = parameter.hash {
+        listing.hash = Some(hash);
+    }
+
+    logger.log(&Event::ProductUpdated(ProductUpdatedEvent {
+        product_id: parameter.product_id,
+        amount:     listing.amount,
+        wallet:     listing.wallet,
+        hash:       listing.hash,
+    }))?;
+    Ok(())
+}
+
+/// Function for the merchant to set or remove the metadata URL of a product.
+/// Logs a `ProductMetadataUrl` event.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - Sender is not an account of the merchant.
+/// - The product is not found.
+/// - The `merchant_id` does not match the one of the listing.
+/// - The product is cancelled.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "set_product_metadata_url",
+    parameter = "SetProductMetadataUrlParameter",
+    mutable,
+    enable_logger
+)]
+fn set_product_metadata_url(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: SetProductMetadataUrlParameter = ctx.parameter_cursor().get()?;
+    internal_set_product_metadata_url(host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `set_product_metadata_url` and the permit message
+fn internal_set_product_metadata_url(
+    host: &mut Host<State>,
+    parameter: SetProductMetadataUrlParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    host.state().ensure_merchant(&parameter.merchant_id, &sender)?;
+
+    let mut listing = host
+        .state_mut()
+        .product_listings
+        .get_mut(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(parameter.merchant_id == listing.merchant_id, MarketplaceError::UnauthorizedMerchant);
+    ensure!(listing.state != ProductState::Cancelled, MarketplaceError::InvalidProductState);
+    listing.metadata_url = parameter.metadata_url.clone();
+
+    logger.log(&Event::ProductMetadataUrl(ProductMetadataUrlEvent {
+        product_id:   parameter.product_id,
+        metadata_url: parameter.metadata_url,
+    }))?;
+    Ok(())
+}
+
+/// View function for buyers to prove that a catalogue entry is the one the
+/// product was listed with. Returns `false` if it differs or the listing has
+/// no hash.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The product is not found.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "verify_product_hash",
+    parameter = "VerifyProductHashParameter",
+    return_value = "bool",
+    error = "MarketplaceError",
+    crypto_primitives
+)]
+fn verify_product_hash(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> Result<bool, MarketplaceError> {
+    let parameter: VerifyProductHashParameter = ctx.parameter_cursor().get()?;
+    let listing = host
+        .state()
+        .product_listings
+        .get(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    Ok(listing.hash == Some(parameter.metadata.hash(crypto_primitives)))
+}
+
+
+
+/// Function to cancel or unlist a product. Orders of the product that were not
+/// shipped yet are refunded to their buyers. Only accounts of the merchant can
+/// cancel its products, and auctions only as long as nobody bid.
+#[receive(contract = "gonana_marketplace", name = "cancel_product", parameter = "CancelProductParameter", mutable, enable_logger)]
+fn cancel_product(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError>{
+    let parameter: CancelProductParameter = ctx.parameter_cursor().get()?;
+    internal_cancel_product(ctx, host, parameter, ctx.sender(), logger)
+}
+
+/// Function to refund an open order back to the buyer. The ordered units are
+/// returned to the stock of the listing. Besides the merchant, addresses with
+/// the `Moderator` role can refund any order.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The order is not found or is not escrowed or shipped.
+/// - Sender is not a moderator, and the `merchant_id` does not match the one
+///   of the listing or sender is not an account of the merchant.
+/// - The refund fails.
+#[receive(contract = "gonana_marketplace", name = "refund_order", parameter = "RefundOrderParameter", mutable, enable_logger)]
+fn refund_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: RefundOrderParameter = ctx.parameter_cursor().get()?;
+    internal_refund_order(ctx, host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `refund_order` and the permit message
+fn internal_refund_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: RefundOrderParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let state = host.state();
+
+    let order = state.orders.get(&parameter.order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(
+        matches!(order.status, OrderStatus::Escrowed | OrderStatus::Shipped),
+        MarketplaceError::InvalidOrderStatus
+    );
+    let listing = state
+        .product_listings
+        .get(&order.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    let is_merchant = parameter.merchant_id == listing.merchant_id
+        && state.is_merchant_account(&parameter.merchant_id, &sender);
+    ensure!(is_merchant || state.has_role(&sender, Role::Moderator), MarketplaceError::UnauthorizedMerchant);
+    drop(listing);
+    drop(order);
+
+    refund_escrow(ctx, host, parameter.order_id, logger)
+}
+
+/// Pays `amount` of `currency` held by the marketplace to an account. CIS-2
+/// tokens are paid out by calling `transfer` on their token contract.
+///
+/// It rejects if:
+/// - The transfer fails.
+fn transfer_funds(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    currency: Currency,
+    to: AccountAddress,
+    amount: Amount,
+) -> Result<(), MarketplaceError> {
+    match currency {
+        Currency::Ccd => host.invoke_transfer(&to, amount)?,
+        Currency::Cis2 {
+            contract,
+            token_id,
+        } => {
+            let transfer = Transfer {
+                token_id,
+                amount:   ContractTokenAmount::from(amount.micro_ccd),
+                from:     Address::Contract(ctx.self_address()),
+                to:       Receiver::Account(to),
+                data:     AdditionalData::empty(),
+            };
+            host.invoke_contract(
+                &contract,
+                &TransferParams(vec![transfer]),
+                EntrypointName::new_unchecked("transfer"),
+                Amount::zero(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Pulls the price of a sponsored order from the signer into escrow. The
+/// marketplace transfers the tokens as an operator of the signer to its own
+/// `onReceivingCIS2` hook with the order as `data`, so the order is placed
+/// and escrowed like any other token payment within the permit transaction.
+///
+/// It rejects if:
+/// - The product is not found, not listed or has not enough stock.
+/// - The product is priced in CCD, which cannot be pulled from an account.
+/// - The transfer fails, e.g. the marketplace is not an operator of the
+///   signer or the signer does not hold enough tokens.
+fn pull_order_payment(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: PlaceOrderParameter,
+    signer: AccountAddress,
+) -> Result<(), MarketplaceError> {
+    let product = host
+        .state()
+        .product_listings
+        .get(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    // Check these here, as rejections of the hook are reported by the token
+    // contract as a failed transfer.
+    ensure!(product.auction.is_none(), MarketplaceError::AuctionListing);
+    ensure!(product.state == ProductState::Listed, MarketplaceError::InvalidProductState);
+    ensure!(parameter.quantity <= product.quantity, MarketplaceError::InsufficientStock);
+    let price = product.price_of(parameter.quantity)?;
+    let (contract, token_id) = match product.currency.clone() {
+        Currency::Cis2 {
+            contract,
+            token_id,
+        } => (contract, token_id),
+        Currency::Ccd => bail!(MarketplaceError::WrongCurrency),
+    };
+    drop(product);
+
+    let transfer = Transfer {
+        token_id,
+        amount: ContractTokenAmount::from(price.micro_ccd),
+        from: Address::Account(signer),
+        to: Receiver::Contract(
+            ctx.self_address(),
+            OwnedEntrypointName::new_unchecked("onReceivingCIS2".into()),
+        ),
+        data: AdditionalData::from(to_bytes(&parameter)),
+    };
+    host.invoke_contract(
+        &contract,
+        &TransferParams(vec![transfer]),
+        EntrypointName::new_unchecked("transfer"),
+        Amount::zero(),
+    )
+    .map_err(|_| MarketplaceError::BuyerNotFunded)?;
+    Ok(())
+}
+
+/// Escrows a payment of `paid` in `currency` for a new order. Any amount paid
+/// above the price is sent back to the payer.
+///
+/// It rejects if:
+/// - The product is not found or not listed.
+/// - The product is priced in another currency.
+/// - Less than the price of the ordered units was paid.
+/// - More than the price was paid by a contract, which cannot be refunded.
+/// - The order has no `buyer_address` and was paid by a contract.
+/// - There is not enough stock.
+/// - Refunding the excess fails.
+fn escrow_order(
     ctx: &ReceiveContext,
-    host: &Host<State>,
-) -> Result<PublicKeyOfQueryResponse,MarketplaceError> {
-    // Parse the parameter.
-    let params: VecOfAccountAddresses = ctx.parameter_cursor().get()?;
-    // Build the response.
-    let mut response: Vec<Option<AccountPublicKeys>> = Vec::with_capacity(params.queries.len());
-    for account in params.queries {
-        // Query the public_key.
-        let public_keys = host.account_public_keys(account).ok();
+    host: &mut Host<State>,
+    mut parameter: PlaceOrderParameter,
+    currency: Currency,
+    paid: Amount,
+    payer: Address,
+    logger: &mut impl HasLogger,
+) -> Result<OrderId, MarketplaceError> {
+    // Find the product by name
+    let product = 
+        host.state()
+        .product_listings
+        .get(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(product.auction.is_none(), MarketplaceError::AuctionListing);
+    ensure!(product.currency == currency, MarketplaceError::WrongCurrency);
+    let price = product.price_of(parameter.quantity)?;
+    drop(product);
 
-        response.push(public_keys);
+    // Ensure that the full amount was paid
+    let excess = paid.checked_sub(price).ok_or(MarketplaceError::InvalidPrice)?;
+    // Only accounts can receive the excess back
+    let payer = match payer {
+        Address::Account(account) => Some(account),
+        Address::Contract(_) => None,
+    };
+    ensure!(excess == Amount::zero() || payer.is_some(), MarketplaceError::InvalidPrice);
+    // The escrow is refunded to the `buyer_address`, which defaults to the
+    // paying account
+    parameter.buyer_address = parameter.buyer_address.or(payer);
+    ensure!(parameter.buyer_address.is_some(), MarketplaceError::MissingBuyerAddress);
+
+    // Create an order, escrowing only the price of the ordered units
+    let (state, builder) = host.state_and_builder();
+    let order_id = state.create_order(parameter, price, ctx.metadata().slot_time(), builder, logger)?;
+
+    if let Some(payer) = payer.filter(|_| excess > Amount::zero()) {
+        transfer_funds(ctx, host, currency.clone(), payer, excess)?;
+        logger.log(&Event::ExcessRefund(ExcessRefundEvent {
+            order_id,
+            to: payer,
+            amount: excess,
+        }))?;
     }
-    let result = PublicKeyOfQueryResponse::from(response);
-    Ok(result)
+    Ok(order_id)
 }
 
-/// Response type for the function `nonceOf`.
-#[derive(Debug, Serialize, SchemaType)]
-#[concordium(transparent)]
-pub struct NonceOfQueryResponse(#[concordium(size_length = 2)] pub Vec<u64>);
+// buy a product, any amount paid above the price is sent back to the sender.
+// Returns the id of the new order.
+#[receive(contract = "gonana_marketplace", name="place_order", parameter = "PlaceOrderParameter", return_value = "OrderId", mutable, payable, enable_logger)]
+fn place_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<OrderId, MarketplaceError> {
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
 
-impl From<Vec<u64>> for NonceOfQueryResponse {
-    fn from(results: concordium_std::Vec<u64>) -> Self { NonceOfQueryResponse(results) }
+    let parameter: PlaceOrderParameter = ctx.parameter_cursor().get()?;
+    escrow_order(ctx, host, parameter, Currency::Ccd, amount, ctx.sender(), logger)
 }
 
-/// Get the nonces of accounts.
+/// Receive hook called by a CIS-2 token contract when tokens are transferred
+/// to the marketplace. The `data` of the transfer is the serialized
+/// `PlaceOrderParameter` of the order paid for; the received tokens are
+/// escrowed like CCD attached to `place_order`, and any tokens above the
+/// price are sent back to the `from` address.
 ///
 /// It rejects if:
-/// - It fails to parse the parameter.
+/// - The contract is paused.
+/// - It fails to parse the parameter or the order parameter in `data`.
+/// - The sender is not a contract.
+/// - The product is not priced in the received token.
+/// - Placing the order fails.
 #[receive(
     contract = "gonana_marketplace",
-    name = "nonceOf",
-    parameter = "VecOfAccountAddresses",
-    return_value = "NonceOfQueryResponse",
-    error = "MaketplaceError"
+    name = "onReceivingCIS2",
+    parameter = "OnReceivingCis2Params<ContractTokenId, ContractTokenAmount>",
+    mutable,
+    enable_logger
 )]
-fn contract_nonce_of(
+fn on_receiving_cis2(
     ctx: &ReceiveContext,
-    host: &Host<State>,
-) -> Result<NonceOfQueryResponse,MarketplaceError> {
-    // Parse the parameter.
-    let params: VecOfAccountAddresses = ctx.parameter_cursor().get()?;
-    // Build the response.
-    let mut response: Vec<u64> = Vec::with_capacity(params.queries.len());
-    for account in params.queries {
-        // Query the next nonce.
-        let nonce = host.state().nonces_registry.get(&account).map(|nonce| *nonce).unwrap_or(0);
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
+
+    let params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount> =
+        ctx.parameter_cursor().get()?;
+    // The token is identified by the contract calling the hook.
+    let contract = match ctx.sender() {
+        Address::Contract(contract) => contract,
+        Address::Account(_) => bail!(MarketplaceError::UnsupportedToken),
+    };
+    let currency = Currency::Cis2 {
+        contract,
+        token_id: params.token_id,
+    };
 
-        response.push(nonce);
-    }
-    Ok(NonceOfQueryResponse::from(response))
+    let parameter: PlaceOrderParameter = from_bytes(params.data.as_ref())?;
+    let paid = Amount::from_micro_ccd(params.amount.into());
+    escrow_order(ctx, host, parameter, currency, paid, params.from, logger)?;
+    Ok(())
 }
 
+/// Function to bid in an auction with the attached CCD. The bid is held in
+/// escrow and the previous highest bid is sent back to its bidder.
+///
+/// It rejects if:
+/// - The contract is paused.
+/// - It fails to parse the parameter.
+/// - Sender is not an account.
+/// - The product is not found, not listed or not sold by auction.
+/// - The auction ended.
+/// - The bid is below the reserve price, or does not exceed the highest bid
+///   by at least the minimum increment.
+/// - Refunding the previous highest bid fails.
+#[receive(contract = "gonana_marketplace", name = "bid", parameter = "BidParameter", mutable, payable, enable_logger)]
+fn bid(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that contract is not paused.
+    ensure!(!host.state().paused, MarketplaceError::ContractPaused);
+
+    let parameter: BidParameter = ctx.parameter_cursor().get()?;
+    // Only accounts can be refunded when outbid
+    let bidder = match ctx.sender() {
+        Address::Account(account) => account,
+        Address::Contract(_) => bail!(MarketplaceError::Unauthorized),
+    };
+    let now = ctx.metadata().slot_time();
+
+    let mut listing = host
+        .state_mut()
+        .product_listings
+        .get_mut(&parameter.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(listing.state == ProductState::Listed, MarketplaceError::InvalidProductState);
+    let reserve = listing.price_of(listing.quantity)?;
+    let auction = listing.auction.as_mut().ok_or(MarketplaceError::NotAnAuction)?;
+    ensure!(now < auction.end, MarketplaceError::AuctionEnded);
+
+    let minimum = match &auction.highest_bid {
+        Some(highest) => {
+            highest.amount.micro_ccd.checked_add(auction.min_increment.micro_ccd).ok_or(MarketplaceError::BidTooLow)?
+        }
+        None => reserve.micro_ccd,
+    };
+    ensure!(amount.micro_ccd >= minimum, MarketplaceError::BidTooLow);
+    let outbid = auction.highest_bid.replace(Bid {
+        bidder,
+        buyer_id: parameter.buyer_id,
+        amount,
+    });
+    drop(listing);
 
+    logger.log(&Event::BidPlaced(BidEvent {
+        product_id: parameter.product_id.clone(),
+        bidder,
+        amount,
+    }))?;
+
+    if let Some(outbid) = outbid {
+        transfer_funds(ctx, host, Currency::Ccd, outbid.bidder, outbid.amount)?;
+        logger.log(&Event::BidRefunded(BidEvent {
+            product_id: parameter.product_id,
+            bidder:     outbid.bidder,
+            amount:     outbid.amount,
+        }))?;
+    }
+    Ok(())
+}
 
-/// Helper function to calculate the `message_hash`.
+/// Function to settle an auction after it ended. The winning bid becomes an
+/// escrowed order of all units for the winner, which then follows the normal
+/// order flow. Anyone can settle an auction, also while the contract is
+/// paused, as the winning bid is already escrowed. Returns the id of the new
+/// order.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The product is not found, not listed or not sold by auction.
+/// - The auction has not ended yet.
+/// - The auction ended without bids.
 #[receive(
     contract = "gonana_marketplace",
-    name = "viewMessageHash",
-    parameter = "PermitParam",
-    return_value = "[u8;32]",
-    crypto_primitives,
-    mutable
+    name = "settle_auction",
+    parameter = "SettleAuctionParameter",
+    return_value = "OrderId",
+    mutable,
+    enable_logger
 )]
-fn contract_view_message_hash(
+fn settle_auction(
     ctx: &ReceiveContext,
-    _host: &mut Host<State>,
-    crypto_primitives: &impl HasCryptoPrimitives,
-) -> Result<[u8; 32],MarketplaceError> {
-    // Parse the parameter.
-    let mut cursor = ctx.parameter_cursor();
-    // The input parameter is `PermitParam` but we only read the initial part of it
-    // with `PermitParamPartial`. I.e. we read the `signature` and the
-    // `signer`, but not the `message` here.
-    let param: PermitParamPartial = cursor.get()?;
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<OrderId, MarketplaceError> {
+    let parameter: SettleAuctionParameter = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().slot_time();
+    let (state, builder) = host.state_and_builder();
+
+    let mut listing =
+        state.product_listings.get_mut(&parameter.product_id).ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(listing.state == ProductState::Listed, MarketplaceError::InvalidProductState);
+    let quantity = listing.quantity;
+    let auction = listing.auction.as_mut().ok_or(MarketplaceError::NotAnAuction)?;
+    ensure!(now >= auction.end, MarketplaceError::AuctionNotEnded);
+    let winner = auction.highest_bid.take().ok_or(MarketplaceError::NoBids)?;
+    drop(listing);
+
+    // The winning bid is already held by the marketplace and becomes the
+    // escrow of the order
+    let params = PlaceOrderParameter {
+        product_id: parameter.product_id,
+        buyer_address: Some(winner.bidder),
+        buyer_id: winner.buyer_id,
+        amount: winner.amount,
+        quantity,
+    };
+    state.create_order(params, winner.amount, now, builder, logger)
+}
 
-    // The input parameter is `PermitParam` but we have only read the initial part
-    // of it with `PermitParamPartial` so far. We read in the `message` now.
-    // `(cursor.size() - cursor.cursor_position()` is the length of the message in
-    // bytes.
-    let mut message_bytes = vec![0; (cursor.size() - cursor.cursor_position()) as usize];
+/// Releases the escrow of an escrowed or shipped order: the escrowed amount,
+/// minus the platform fee, is paid to the merchant wallet and the order is
+/// kept with the status `Confirmed`. The state is only updated once the
+/// payout succeeded, so a failing payout leaves the order unchanged.
+///
+/// It rejects if:
+/// - The order is not found or is not escrowed or shipped.
+/// - The product of the order is not found.
+/// - The transfer to the merchant fails.
+fn settle_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    order_id: OrderId,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let state = host.state();
 
-    cursor.read_exact(&mut message_bytes)?;
+    let order = state.orders.get(&order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    // Ensure that the order is in a valid status for confirming the escrow
+    ensure!(
+        matches!(order.status, OrderStatus::Escrowed | OrderStatus::Shipped),
+        MarketplaceError::InvalidOrderStatus
+    );
+    let (currency, amount, fee_bps) = (order.currency.clone(), order.amount, order.fee_bps);
+    let product_id = order.product_id.clone();
+    drop(order);
 
-    // The message signed in the Concordium browser wallet is prepended with the
-    // `account` address and 8 zero bytes. Accounts in the Concordium browser wallet
-    // can either sign a regular transaction (in that case the prepend is
-    // `account` address and the nonce of the account which is by design >= 1)
-    // or sign a message (in that case the prepend is `account` address and 8 zero
-    // bytes). Hence, the 8 zero bytes ensure that the user does not accidentally
-    // sign a transaction. The account nonce is of type u64 (8 bytes).
-    let mut msg_prepend = [0; 32 + 8];
-    // Prepend the `account` address of the signer.
-    msg_prepend[0..32].copy_from_slice(param.signer.as_ref());
-    // Prepend 8 zero bytes.
-    msg_prepend[32..40].copy_from_slice(&[0u8; 8]);
-    // Calculate the message hash.
-    let message_hash =
-        crypto_primitives.hash_sha2_256(&[&msg_prepend[0..40], &message_bytes].concat()).0;
+    let merchant_address = state
+        .product_listings
+        .get(&product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?
+        .wallet
+        .ok_or(MarketplaceError::MissingWallet)?;
 
-    Ok(message_hash)
+    let fee = fee_of(amount, fee_bps);
+    let payout = amount - fee;
+    // Transfer funds
+    transfer_funds(ctx, host, currency.clone(), merchant_address, payout)?;
+
+    let state_mut = host.state_mut();
+    state_mut.orders.get_mut(&order_id).ok_or(MarketplaceError::OrderNotFound)?.status = OrderStatus::Confirmed;
+    state_mut.keep_fee(currency, fee);
+    state_mut.unindex_if_settled(&product_id);
+    logger.log(&Event::OrderConfirmed(OrderConfirmedEvent {
+        order_id,
+        payout,
+        fee,
+    }))?;
+    Ok(())
+}
+
+/// Checks that `sender` may confirm an order, i.e. it is the `buyer_address`
+/// of the order or an approved relayer, and that the order is escrowed or
+/// shipped. These are all the checks of `settle_order` apart from the
+/// payout itself.
+fn ensure_can_confirm(state: &State, order_id: OrderId, sender: Address) -> Result<(), MarketplaceError> {
+    let order = state.orders.get(&order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(
+        matches!(order.status, OrderStatus::Escrowed | OrderStatus::Shipped),
+        MarketplaceError::InvalidOrderStatus
+    );
+    ensure!(state.product_listings.get(&order.product_id).is_some(), MarketplaceError::ProductNotFound);
+    let is_buyer = order.buyer_address.map(Address::Account) == Some(sender);
+    ensure!(is_buyer || state.has_role(&sender, Role::Relayer), MarketplaceError::UnauthorizedConfirmation);
+    Ok(())
+}
+
+//function to confirm an escrow, only the buyer or an approved relayer can confirm
+#[receive(contract = "gonana_marketplace", name = "confirm_order", parameter = "OrderParameter", mutable, enable_logger)]
+fn confirm_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let param: OrderParameter = ctx.parameter_cursor().get()?;
+    internal_confirm_order(ctx, host, param, ctx.sender(), logger)
 }
 
+// internal function shared by `confirm_order` and the permit message
+fn internal_confirm_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    param: OrderParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+     ensure_can_confirm(host.state(), param.order_id, sender)?;
+     settle_order(ctx, host, param.order_id, logger)
+}
 
+/// Function to confirm several orders in one transaction, e.g. the deliveries
+/// of a day. Returns the outcome of every order, in the order given.
+///
+/// In `BestEffort` mode orders that cannot be confirmed, i.e. orders that are
+/// not found, not escrowed or shipped, not confirmable by the sender, or whose
+/// payout to the merchant fails, e.g. while the token contract is paused, are
+/// skipped and left unchanged.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - In `AllOrNothing` mode, any order cannot be confirmed.
 #[receive(
     contract = "gonana_marketplace",
-    name = "permit",
-    parameter = "PermitParam",
-    crypto_primitives,
+    name = "confirm_orders",
+    parameter = "ConfirmOrdersParameter",
+    return_value = "Vec<BatchItemResult>",
     mutable,
     enable_logger
 )]
-fn contract_permit(
+fn confirm_orders(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<Vec<BatchItemResult>, MarketplaceError> {
+    let parameter: ConfirmOrdersParameter = ctx.parameter_cursor().get()?;
+    let sender = ctx.sender();
+
+    let mut results = Vec::with_capacity(parameter.order_ids.len());
+    for order_id in parameter.order_ids {
+        let confirmed = ensure_can_confirm(host.state(), order_id, sender)
+            .and_then(|()| settle_order(ctx, host, order_id, logger));
+        match confirmed {
+            Ok(()) => results.push(BatchItemResult::Ok),
+            Err(error) if parameter.mode == BatchMode::BestEffort => {
+                results.push(BatchItemResult::Failed(error))
+            }
+            Err(error) => bail!(error),
+        }
+    }
+    Ok(results)
+}
+
+/// Function for the merchant to mark an escrowed order as shipped. This
+/// starts the `CONFIRMATION_WINDOW` of the buyer.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - Sender is not an account of the merchant.
+/// - The order is not found or is not escrowed.
+/// - The `merchant_id` does not match the one of the listing.
+/// - The delivery deadline of the order has passed.
+#[receive(contract = "gonana_marketplace", name = "mark_shipped", parameter = "ShipOrderParameter", mutable, enable_logger)]
+fn mark_shipped(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: ShipOrderParameter = ctx.parameter_cursor().get()?;
+    internal_mark_shipped(ctx, host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `mark_shipped` and the permit message
+fn internal_mark_shipped(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: ShipOrderParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    host.state().ensure_merchant(&parameter.merchant_id, &sender)?;
+    let now = ctx.metadata().slot_time();
+    let state_mut = host.state_mut();
+
+    let mut order = state_mut
+        .orders
+        .get_mut(&parameter.order_id)
+        .ok_or(MarketplaceError::OrderNotFound)?;
+    let listing = state_mut
+        .product_listings
+        .get(&order.product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?;
+    ensure!(parameter.merchant_id == listing.merchant_id, MarketplaceError::UnauthorizedMerchant);
+    drop(listing);
+
+    ensure!(order.status == OrderStatus::Escrowed, MarketplaceError::InvalidOrderStatus);
+    ensure!(now <= order.delivery_deadline, MarketplaceError::DeadlinePassed);
+
+    let confirmation_deadline = deadline_after(now, CONFIRMATION_WINDOW);
+    order.status = OrderStatus::Shipped;
+    order.shipped_at = Some(now);
+    order.confirmation_deadline = Some(confirmation_deadline);
+
+    logger.log(&Event::OrderShipped(OrderShippedEvent {
+        order_id: parameter.order_id,
+        confirmation_deadline,
+    }))?;
+    Ok(())
+}
+
+/// Function for the buyer to reclaim the escrowed funds of an order that was
+/// not shipped before its delivery deadline. The ordered units are returned
+/// to the stock of the listing.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The order is not found or is not escrowed.
+/// - The sender is not the `buyer_address` of the order.
+/// - The delivery deadline of the order has not passed yet.
+/// - The refund fails.
+#[receive(contract = "gonana_marketplace", name = "reclaim_order", parameter = "OrderParameter", mutable, enable_logger)]
+fn reclaim_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: OrderParameter = ctx.parameter_cursor().get()?;
+    internal_reclaim_order(ctx, host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `reclaim_order` and the permit message
+fn internal_reclaim_order(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: OrderParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let order = host.state().orders.get(&parameter.order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(
+        order.buyer_address.map(Address::Account) == Some(sender),
+        MarketplaceError::Unauthorized
+    );
+    ensure!(order.status == OrderStatus::Escrowed, MarketplaceError::InvalidOrderStatus);
+    ensure!(ctx.metadata().slot_time() > order.delivery_deadline, MarketplaceError::DeadlineNotReached);
+    drop(order);
+
+    refund_escrow(ctx, host, parameter.order_id, logger)
+}
+
+/// Function to release the escrow of a shipped order to the merchant once the
+/// confirmation window of the buyer has passed without a confirmation.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The order is not found or is not shipped.
+/// - The confirmation window has not passed yet.
+/// - The payout fails.
+#[receive(contract = "gonana_marketplace", name = "claim_payout", parameter = "OrderParameter", mutable, enable_logger)]
+fn claim_payout(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: OrderParameter = ctx.parameter_cursor().get()?;
+    internal_claim_payout(ctx, host, parameter, logger)
+}
+
+// internal function shared by `claim_payout` and the permit message
+fn internal_claim_payout(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: OrderParameter,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let order = host
+        .state()
+        .orders
+        .get(&parameter.order_id)
+        .ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(order.status == OrderStatus::Shipped, MarketplaceError::InvalidOrderStatus);
+    let confirmation_deadline = order.confirmation_deadline.ok_or(MarketplaceError::OrderNotShipped)?;
+    ensure!(ctx.metadata().slot_time() > confirmation_deadline, MarketplaceError::DeadlineNotReached);
+    drop(order);
+
+    settle_order(ctx, host, parameter.order_id, logger)
+}
+
+
+
+
+/// Grant a role to an address. Logs a `GrantRole` event.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "grantRole", parameter = "RoleParameter", mutable, enable_logger)]
+fn contract_grant_role(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: RoleParameter = ctx.parameter_cursor().get()?;
+    internal_grant_role(host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `grantRole` and the permit message
+fn internal_grant_role(
+    host: &mut Host<State>,
+    parameter: RoleParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to grant roles.
+    ensure!(host.state().has_role(&sender, Role::Admin), MarketplaceError::Unauthorized);
+
+    host.state_mut().grant_role(parameter.address, parameter.role);
+
+    logger.log(&Event::GrantRole(RoleEvent {
+        address: parameter.address,
+        role:    parameter.role,
+    }))?;
+    Ok(())
+}
+
+/// Revoke a role from an address. Logs a `RevokeRole` event.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+/// - An admin tries to revoke its own `Admin` role.
+#[receive(contract = "gonana_marketplace", name = "revokeRole", parameter = "RoleParameter", mutable, enable_logger)]
+fn contract_revoke_role(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: RoleParameter = ctx.parameter_cursor().get()?;
+    internal_revoke_role(host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `revokeRole` and the permit message
+fn internal_revoke_role(
+    host: &mut Host<State>,
+    parameter: RoleParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to revoke roles.
+    ensure!(host.state().has_role(&sender, Role::Admin), MarketplaceError::Unauthorized);
+
+    // Prevent the contract from being left without an admin by accident.
+    ensure!(
+        !(parameter.role == Role::Admin && parameter.address == sender),
+        MarketplaceError::CannotRevokeOwnAdmin
+    );
+    host.state_mut().revoke_role(&parameter.address, parameter.role);
+
+    logger.log(&Event::RevokeRole(RoleEvent {
+        address: parameter.address,
+        role:    parameter.role,
+    }))?;
+    Ok(())
+}
+
+/// Check if an address has been granted a role.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "hasRole", parameter = "RoleParameter", return_value = "bool")]
+fn contract_has_role(ctx: &ReceiveContext, host: &Host<State>) -> Result<bool, MarketplaceError> {
+    let parameter: RoleParameter = ctx.parameter_cursor().get()?;
+    Ok(host.state().has_role(&parameter.address, parameter.role))
+}
+
+/// Get all roles granted to an address.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "getRoles", parameter = "Address", return_value = "Vec<Role>")]
+fn contract_get_roles(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<Role>, MarketplaceError> {
+    let address: Address = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    Ok(ROLES.into_iter().filter(|role| state.has_role(&address, *role)).collect())
+}
+
+/// Function for the buyer or the merchant to dispute an escrowed or shipped
+/// order. While the dispute is open the order can neither be confirmed,
+/// refunded nor paid out; only the arbiter can settle it.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The order is not found or is not escrowed or shipped.
+/// - The sender is neither the `buyer_address` of the order nor an account of
+///   the merchant of the listing.
+#[receive(contract = "gonana_marketplace", name = "open_dispute", parameter = "OpenDisputeParameter", mutable, enable_logger)]
+fn open_dispute(
     ctx: &ReceiveContext,
     host: &mut Host<State>,
     logger: &mut impl HasLogger,
-    crypto_primitives: &impl HasCryptoPrimitives,
-) -> Result<(),MarketplaceError> {
-
-    // Parse the parameter.
-    let param: PermitParam = ctx.parameter_cursor().get()?;
-
-    // Update the nonce.
-    let mut entry = host.state_mut().nonces_registry.entry(param.signer).or_insert_with(|| 0);
-
-    // Get the current nonce.
-    let nonce = *entry;
-    // Bump nonce.
-    *entry += 1;
-    drop(entry);
-
-    let message = param.message;
-    let list_param = message.payload.clone();
+) -> Result<(), MarketplaceError> {
+    let parameter: OpenDisputeParameter = ctx.parameter_cursor().get()?;
+    internal_open_dispute(host, parameter, ctx.sender(), logger)
+}
 
-    // Check the nonce to prevent replay attacks.
-    ensure_eq!(message.nonce, nonce, MarketplaceError::NonceAlreadyUsed);
+// internal function shared by `open_dispute` and the permit message
+fn internal_open_dispute(
+    host: &mut Host<State>,
+    parameter: OpenDisputeParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let state = host.state();
 
-     // Check that the signature was intended for this contract.
-     ensure_eq!(
-        message.contract_address,
-        ctx.self_address(),
-        MarketplaceError::WrongContract.into()
+    let order = state.orders.get(&parameter.order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(
+        matches!(order.status, OrderStatus::Escrowed | OrderStatus::Shipped),
+        MarketplaceError::InvalidOrderStatus
     );
-    // Check signature is not expired.
-    ensure!(message.timestamp > ctx.metadata().slot_time(), MarketplaceError::Expired.into());
-    let _message_hash = contract_view_message_hash(ctx, host, crypto_primitives)?;
-
-    // Check signature.
-    let valid_signature = host.check_account_signature(param.signer, &param.signature, &list_param)
-        .expect("account signature was incorrect");
-    ensure!(valid_signature, MarketplaceError::WrongSignature);
-    
-    //Execute Function Calls
-    if message.entry_point.as_entrypoint_name() == EntrypointName::new_unchecked("internal_list_product") {
-        let params: ListProductParameter = from_bytes(&message.payload).expect("could not unwrap payload");
-        let response = internal_list_product(host, params)?;
-        // Log the nonce event.
-        logger.log(&Event::Nonce(NonceEvent {
-            account: param.signer,
-            nonce,
-        })).expect("events could not be logged");
-        Ok(response)
-
-    }else if message.entry_point.as_entrypoint_name() == EntrypointName::new_unchecked("place_order") {
-        let params: PlaceOrderParameter = from_bytes(&message.payload).expect("could not unwrap payload");
-        let state_mut = host.state_mut();
-        // Find the product by name
-        let mut product = 
-            state_mut
-            .product_listings
-            .get_mut(&params.product_id)
-            .ok_or(MarketplaceError::ProductNotFound)?;
-    
-        // Ensure that the product is in a valid state for placing an order
-        ensure!(product.state == ProductState::Listed, MarketplaceError::InvalidProductState);    
-        let order = Order {
-            buyer_address:   params.buyer_address,
-            product_id: product.product_id.clone(),
-            amount: product.amount,
-            buyer_id: params.buyer_id
-        }; 
-        // Insert the order and update the product state to Escrowed 
-        ensure!(state_mut.orders.insert(params.product_id.clone(), order).is_none(), MarketplaceError::OrderAlreadyExists);    
-        product.state = ProductState::Escrowed;    
-        Ok(())
 
-        // CANCEL PLACED ORDERS!!!!!
-    }else if message.entry_point.as_entrypoint_name() == EntrypointName::new_unchecked("cancel_order") {
-        let params: CancelProductParameter = from_bytes(&message.payload).expect("could not unwrap payload");
-        let product_name = params.product_id.clone();
+    let listing = state.product_listings.get(&order.product_id).ok_or(MarketplaceError::ProductNotFound)?;
+    let is_buyer = order.buyer_address.map(Address::Account) == Some(sender);
+    let is_merchant = state.is_merchant_account(&listing.merchant_id, &sender);
+    ensure!(is_buyer || is_merchant, MarketplaceError::Unauthorized);
+    drop(listing);
+    drop(order);
+
+    let mut order = host
+        .state_mut()
+        .orders
+        .get_mut(&parameter.order_id)
+        .ok_or(MarketplaceError::OrderNotFound)?;
+    order.status = OrderStatus::Disputed;
+
+    logger.log(&Event::DisputeOpened(DisputeOpenedEvent {
+        order_id:   parameter.order_id,
+        opened_by:  sender,
+        reason:     parameter.reason,
+    }))?;
+    Ok(())
+}
 
-    // Check if the product is found
-    if let Some(mut listing) = host.state_mut().product_listings.get_mut(&product_name) {
-        // Check if the product is in a cancellable state
-        ensure!(params.merchant_id == listing.merchant_id, MarketplaceError::WrongSignature);
+/// Function for the arbiter to settle a disputed order by splitting the
+/// escrowed amount between the buyer and the merchant. The platform fee is
+/// deducted from the share of the merchant. The order is kept with the status
+/// `Resolved`.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - Sender does not have the `Arbiter` role.
+/// - The order is not found or is not disputed.
+/// - `buyer_amount` is larger than the escrowed amount.
+/// - The order holds a share for the buyer but has no `buyer_address`.
+/// - Any of the transfers fail.
+#[receive(contract = "gonana_marketplace", name = "resolve_dispute", parameter = "ResolveDisputeParameter", mutable, enable_logger)]
+fn resolve_dispute(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: ResolveDisputeParameter = ctx.parameter_cursor().get()?;
+    internal_resolve_dispute(ctx, host, parameter, ctx.sender(), logger)
+}
 
-        match listing.state {
-            ProductState::Listed | ProductState::Escrowed => {
-                listing.state = ProductState::Cancelled;
-                Ok(())
-            }
-            _ => Err(MarketplaceError::InvalidProductState),
-            }
-        } else {
-            // Product not found
-            Err(MarketplaceError::ProductNotFound)
-        }
+// internal function shared by `resolve_dispute` and the permit message
+fn internal_resolve_dispute(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: ResolveDisputeParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    ensure!(host.state().has_role(&sender, Role::Arbiter), MarketplaceError::Unauthorized);
 
+    let state_mut = host.state_mut();
+    let mut order = state_mut
+        .orders
+        .get_mut(&parameter.order_id)
+        .ok_or(MarketplaceError::OrderNotFound)?;
+    ensure!(order.status == OrderStatus::Disputed, MarketplaceError::InvalidOrderStatus);
+    order.status = OrderStatus::Resolved;
+    let merchant_share = order
+        .amount
+        .checked_sub(parameter.buyer_amount)
+        .ok_or(MarketplaceError::InvalidPayoutSplit)?;
+    let (currency, buyer_address, fee_bps) = (order.currency.clone(), order.buyer_address, order.fee_bps);
+    let product_id = order.product_id.clone();
+    drop(order);
+
+    let merchant_address = state_mut
+        .product_listings
+        .get(&product_id)
+        .ok_or(MarketplaceError::ProductNotFound)?
+        .wallet
+        .ok_or(MarketplaceError::MissingWallet)?;
+    state_mut.unindex_if_settled(&product_id);
+    let merchant_amount = state_mut.deduct_fee(currency.clone(), merchant_share, fee_bps);
+
+    if parameter.buyer_amount > Amount::zero() {
+        let buyer_address = buyer_address.ok_or(MarketplaceError::MissingBuyerAddress)?;
+        transfer_funds(ctx, host, currency.clone(), buyer_address, parameter.buyer_amount)?;
     }
-    else{
-        Err(MarketplaceError::WrongFunctionCall)
+    if merchant_amount > Amount::zero() {
+        transfer_funds(ctx, host, currency, merchant_address, merchant_amount)?;
     }
 
+    logger.log(&Event::DisputeResolved(DisputeResolvedEvent {
+        order_id: parameter.order_id,
+        buyer_amount: parameter.buyer_amount,
+        merchant_amount,
+    }))?;
+    Ok(())
 }
 
+/// Set the platform fee deducted from every settlement.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+/// - The fee is larger than `MAX_FEE_BPS`.
+#[receive(contract = "gonana_marketplace", name = "set_fee", parameter = "SetFeeParameter", mutable, enable_logger)]
+fn set_fee(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: SetFeeParameter = ctx.parameter_cursor().get()?;
+    internal_set_fee(host, parameter, ctx.sender(), logger)
+}
 
+// internal function shared by `set_fee` and the permit message
+fn internal_set_fee(
+    host: &mut Host<State>,
+    parameter: SetFeeParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to set the fee.
+    ensure!(host.state().has_role(&sender, Role::Admin), MarketplaceError::Unauthorized);
 
-/// Function to list a product in the marketplace
-#[receive(contract = "gonana_marketplace", name = "list_product", parameter = "ListProductParameter", mutable )]
-fn list_product(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), MarketplaceError>{
-    let parameter: ListProductParameter = ctx.parameter_cursor().get()?;
-   
-    //ensure product has not been listed before
-    //can be handled by the web2 backend though
-    //todo!()
-
+    ensure!(parameter.fee_bps <= MAX_FEE_BPS, MarketplaceError::InvalidFee);
+    host.state_mut().fee_bps = parameter.fee_bps;
 
-    // Check if the price is 0
-    if parameter.amount <= Amount::zero() {
-        return Err(MarketplaceError::InvalidPrice);
-    }
+    logger.log(&Event::FeeUpdated(FeeUpdatedEvent {
+        fee_bps: parameter.fee_bps,
+    }))?;
+    Ok(())
+}
 
-    // Check if the product name is empty
-    if parameter.product_id.is_empty() {
-        return Err(MarketplaceError::ParseParams);
-    }
+/// Withdraw accrued platform fees from the treasury.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+/// - The amount is larger than the accrued fees.
+/// - The transfer fails.
+#[receive(contract = "gonana_marketplace", name = "withdraw_fees", parameter = "WithdrawFeesParameter", mutable, enable_logger)]
+fn withdraw_fees(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: WithdrawFeesParameter = ctx.parameter_cursor().get()?;
+    internal_withdraw_fees(ctx, host, parameter, ctx.sender(), logger)
+}
 
-    let listing = ProductListing {
-            merchant_id: parameter.merchant_id,
-            product_id: parameter.product_id.clone(),
-            amount: parameter.amount,
-            wallet: parameter.wallet,
-            hash: parameter.hash,
-            state:ProductState::Listed
-        };
-        
-    host.state_mut().product_listings.insert(parameter.product_id.clone(), listing);
-    Ok(()) 
+// internal function shared by `withdraw_fees` and the permit message
+fn internal_withdraw_fees(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    parameter: WithdrawFeesParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to withdraw fees.
+    ensure!(host.state().has_role(&sender, Role::Admin), MarketplaceError::Unauthorized);
+
+    let mut accrued = host
+        .state_mut()
+        .treasury
+        .get_mut(&parameter.currency)
+        .ok_or(MarketplaceError::InsufficientFunds)?;
+    *accrued = accrued.checked_sub(parameter.amount).ok_or(MarketplaceError::InsufficientFunds)?;
+    drop(accrued);
+
+    transfer_funds(ctx, host, parameter.currency.clone(), parameter.receiver, parameter.amount)?;
+
+    logger.log(&Event::FeesWithdrawn(FeesWithdrawnEvent {
+        currency: parameter.currency,
+        receiver: parameter.receiver,
+        amount:   parameter.amount,
+    }))?;
+    Ok(())
 }
 
+/// View function to get the platform fee and the accrued fees.
+#[receive(contract = "gonana_marketplace", name = "view_fees", return_value = "FeesView")]
+fn view_fees(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<FeesView> {
+    let state = host.state();
+    Ok(FeesView {
+        fee_bps: state.fee_bps,
+        accrued: state.treasury.iter().map(|(currency, amount)| (currency.clone(), *amount)).collect(),
+    })
+}
 
+/// Add a CIS-2 token to or remove it from the token whitelist. Products can
+/// only be listed and ordered in whitelisted tokens; orders already escrowed
+/// in a removed token are still paid out and refunded.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "update_token_whitelist", parameter = "UpdateTokenWhitelistParameter", mutable, enable_logger)]
+fn update_token_whitelist(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: UpdateTokenWhitelistParameter = ctx.parameter_cursor().get()?;
+    internal_update_token_whitelist(host, parameter, ctx.sender(), logger)
+}
 
-/// Function to cancel or unlist a product
-#[receive(contract = "gonana_marketplace", name = "cancel_product", parameter = "CancelProductParameter", mutable )]
-fn cancel_product(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), MarketplaceError>{
-    let parameter: CancelProductParameter = ctx.parameter_cursor().get()?;
-    let product_name = parameter.product_id.clone();
-
-    // Check if the product is found
-    if let Some(mut listing) = host.state_mut().product_listings.get_mut(&product_name) {
-        ensure!(parameter.merchant_id == listing.merchant_id, MarketplaceError::WrongSignature);
-        // Check if the product is in a cancellable state
-        match listing.state {
-            ProductState::Listed | ProductState::Escrowed => {
-                listing.state = ProductState::Cancelled;
-                Ok(())
-            }
-            _ => Err(MarketplaceError::InvalidProductState),
+// internal function shared by `update_token_whitelist` and the permit message
+fn internal_update_token_whitelist(
+    host: &mut Host<State>,
+    parameter: UpdateTokenWhitelistParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to update the whitelist.
+    ensure!(host.state().has_role(&sender, Role::Admin), MarketplaceError::Unauthorized);
+
+    let token = (parameter.contract, parameter.token_id.clone());
+    match parameter.update {
+        WhitelistUpdate::Add => {
+            host.state_mut().token_whitelist.insert(token);
+        }
+        WhitelistUpdate::Remove => {
+            host.state_mut().token_whitelist.remove(&token);
         }
-    } else {
-        // Product not found
-        Err(MarketplaceError::ProductNotFound)
     }
-}
-
-// buy a product
-#[receive(contract = "gonana_marketplace", name="place_order", parameter = "PlaceOrderParameter", mutable, payable)]
-fn place_order(ctx: &ReceiveContext, host: &mut Host<State>, amount: Amount) -> Result<(), MarketplaceError> {
-    let parameter: PlaceOrderParameter = ctx.parameter_cursor().get()?;
 
-    let state_mut = host.state_mut();
+    logger.log(&Event::TokenWhitelistUpdated(TokenWhitelistEvent {
+        update:   parameter.update,
+        contract: parameter.contract,
+        token_id: parameter.token_id,
+    }))?;
+    Ok(())
+}
 
-    // Find the product by name
-    let mut product = 
-        state_mut
-        .product_listings
-        .get_mut(&parameter.product_id)
-        .ok_or(MarketplaceError::ProductNotFound)?;
+/// View function to get the whitelisted CIS-2 tokens.
+#[receive(contract = "gonana_marketplace", name = "view_token_whitelist", return_value = "Vec<(ContractAddress, ContractTokenId)>")]
+fn view_token_whitelist(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Vec<(ContractAddress, ContractTokenId)>> {
+    Ok(host.state().token_whitelist.iter().map(|token| token.clone()).collect())
+}
 
-    // Ensure that the product is in a valid state for placing an order
-    ensure!(product.state == ProductState::Listed, MarketplaceError::InvalidProductState);    
-    // Ensure that the full amount was paid
-    ensure!(amount >= product.amount, MarketplaceError::InvalidPrice);
+/// Pause/Unpause this smart contract instance by the admin. Listing products,
+/// placing orders and bidding (`list_product`, `list_products`,
+/// `place_order`, `onReceivingCIS2`, `bid` and `permit`) cannot be executed
+/// when the contract is paused. Orders already escrowed can still be shipped,
+/// confirmed, refunded, disputed and resolved, and ended auctions settled, so
+/// a pause never locks user funds.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "setPaused", parameter = "SetPausedParams", mutable, enable_logger)]
+fn contract_set_paused(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to pause/unpause the contract.
+    ensure!(host.state().has_role(&ctx.sender(), Role::Admin), MarketplaceError::Unauthorized);
 
-    // Create an order
-    let order = Order {
-        buyer_address:   parameter.buyer_address,
-        product_id: product.product_id.clone(),
-        amount,
-        buyer_id: parameter.buyer_id
-    };
+    // Parse the parameter.
+    let params: SetPausedParams = ctx.parameter_cursor().get()?;
 
-    // Insert the order and update the product state to Escrowed 
-    ensure!(state_mut.orders.insert(parameter.product_id.clone(), order).is_none(), MarketplaceError::OrderAlreadyExists);
+    // Update the paused variable.
+    host.state_mut().paused = params.paused;
 
-    // If the insert is successful, update the product state
-     // Update the product state to Escrowed
-     product.state = ProductState::Escrowed;
+    logger.log(&Event::Paused(params))?;
     Ok(())
-
 }
 
+/// View function to check whether the marketplace is paused.
+#[receive(contract = "gonana_marketplace", name = "view_paused", return_value = "bool")]
+fn view_paused(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<bool> {
+    Ok(host.state().paused)
+}
 
+/// View function to get the layout version of the stored state, i.e.
+/// `STATE_VERSION` of the module the state was last migrated to.
+#[receive(contract = "gonana_marketplace", name = "view_state_version", return_value = "u32")]
+fn view_state_version(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<u32> {
+    Ok(host.state().version)
+}
 
-//function to confirm an escrow 
-#[receive(contract = "gonana_marketplace", name = "confirm_order", parameter = "PlaceOrderParameter", mutable)]
-fn confirm_order(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), MarketplaceError> {
-     let param:PlaceOrderParameter = ctx.parameter_cursor().get()?;
-     let product_id = param.product_id.clone();
-
-     let state_mut = host.state_mut();
-
-      // Find the product by name
-    let mut product = 
-    state_mut
-        .product_listings
-        .remove_and_get(&product_id) //once and order is confirmed, remove from product listing???
-        .ok_or(MarketplaceError::ProductNotFound)?;
+/// Get the supported standards or addresses for a implementation given list of
+/// standard identifiers.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "supports",
+    parameter = "SupportsQueryParams",
+    return_value = "SupportsQueryResponse",
+    error = "MarketplaceError"
+)]
+fn contract_supports(
+    ctx: &ReceiveContext,
+    host: &Host<State>,
+) -> Result<SupportsQueryResponse, MarketplaceError> {
+    // Parse the parameter.
+    let params: SupportsQueryParams = ctx.parameter_cursor().get()?;
 
-    // Ensure that the product is in a valid state for confirming the escrow
-    ensure!(product.state == ProductState::Escrowed, MarketplaceError::InvalidProductState);
-    
-    product.state = ProductState::Confirmed;
-    let _merchant = product.merchant_id.clone();
-    let amount = product.amount.clone();
-    let merchant_address = product.wallet.clone();
-    drop(product);
+    // Build the response.
+    let mut response = Vec::with_capacity(params.queries.len());
+    for std_id in params.queries {
+        if SUPPORTS_STANDARDS.contains(&std_id.as_standard_identifier()) {
+            response.push(SupportResult::Support);
+        } else {
+            response.push(host.state().have_implementors(&std_id));
+        }
+    }
+    let result = SupportsQueryResponse::from(response);
+    Ok(result)
+}
 
+/// Get the entrypoints supported by the `permit` function given a
+/// list of entrypoints.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "supportsPermit",
+    parameter = "SupportsPermitQueryParams",
+    return_value = "SupportsQueryResponse",
+    error = "MarketplaceError"
+)]
+fn contract_supports_permit(
+    ctx: &ReceiveContext,
+    _host: &Host<State>,
+) -> Result<SupportsQueryResponse, MarketplaceError> {
+    // Parse the parameter.
+    let params: SupportsPermitQueryParams = ctx.parameter_cursor().get()?;
 
-     //Get the order by product_name
-     if let Some(order) = host.state_mut().orders.remove_and_get(&param.product_id) {
-        // Transfer funds
-        match merchant_address {
-            Some(wallet) => {
-                host.invoke_transfer(&wallet, amount)?;
-            },
-            None => ()
+    // Build the response.
+    let mut response = Vec::with_capacity(params.queries.len());
+    for entrypoint in params.queries {
+        if SUPPORTS_PERMIT_ENTRYPOINTS.contains(&entrypoint.as_entrypoint_name()) {
+            response.push(SupportResult::Support);
+        } else {
+            response.push(SupportResult::NoSupport);
         }
-        // It's important to call `Deletable::delete` on the value
-        order.delete();
-        Ok(())
-    } else {
-        // Escrow not found
-        Err(MarketplaceError::OrderNotFound)
     }
+    let result = SupportsQueryResponse::from(response);
+    Ok(result)
+}
+
+/// Set the addresses for an implementation given a standard identifier and a
+/// list of contract addresses.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "setImplementors",
+    parameter = "SetImplementorsParams",
+    error = "MarketplaceError",
+    mutable
+)]
+fn contract_set_implementor(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), MarketplaceError> {
+    // Check that only an admin is authorized to set implementors.
+    ensure!(host.state().has_role(&ctx.sender(), Role::Admin), MarketplaceError::Unauthorized);
+    // Parse the parameter.
+    let params: SetImplementorsParams = ctx.parameter_cursor().get()?;
+    // Update the implementors in the state
+    host.state_mut().set_implementors(params.id, params.implementors);
+    Ok(())
 }
 
+/// Upgrade this smart contract instance to a new module and call optionally a
+/// migration function after the upgrade.
+///
+/// It rejects if:
+/// - Sender is not an admin of the contract instance.
+/// - It fails to parse the parameter.
+/// - If the ugrade fails.
+/// - If the migration invoke fails.
+///
+/// This function is marked as `low_level`. This is **necessary** since the
+/// high-level mutable functions store the state of the contract at the end of
+/// execution. This conflicts with migration since the shape of the state
+/// **might** be changed by the migration function. If the state is then written
+/// by this function it would overwrite the state stored by the migration
+/// function.
+#[receive(
+    contract = "gonana_marketplace",
+    name = "upgrade",
+    parameter = "UpgradeParams",
+    low_level
+)]
+fn contract_upgrade(ctx: &ReceiveContext, host: &mut LowLevelHost) -> Result<(), MarketplaceError> {
+    // Read the top-level contract state.
+    let state: State = host.state().read_root()?;
 
+    // Check that only an admin is authorized to upgrade the smart contract.
+    ensure!(state.has_role(&ctx.sender(), Role::Admin), MarketplaceError::Unauthorized);
+    // Parse the parameter.
+    let params: UpgradeParams = ctx.parameter_cursor().get()?;
+    // Trigger the upgrade.
+    host.upgrade(params.module)?;
+    // Call the migration function if provided.
+    if let Some((func, parameters)) = params.migrate {
+        host.invoke_contract_raw(
+            &ctx.self_address(),
+            parameters.as_parameter(),
+            func.as_entrypoint_name(),
+            Amount::zero(),
+        )?;
+    }
+    Ok(())
+}
 
+/// Migrate the stored state to the `State` layout of this module. Called by
+/// `upgrade` in the new module, so escrowed orders and balances are carried
+/// over to it. When the layout changes, keep a copy of the previous layout
+/// (e.g. `StateV1`), bump `STATE_VERSION` and add a match arm converting it.
+///
+/// It rejects if:
+/// - Sender is not this contract instance, i.e. it is not called by `upgrade`.
+/// - The stored state has an unknown layout version.
+///
+/// This function is marked as `low_level` for the same reason as `upgrade`: it
+/// writes the state in the new layout itself.
+#[receive(contract = "gonana_marketplace", name = "migrate", low_level)]
+fn contract_migrate(ctx: &ReceiveContext, host: &mut LowLevelHost) -> Result<(), MarketplaceError> {
+    // Only `upgrade` may trigger a migration.
+    ensure!(ctx.sender() == Address::Contract(ctx.self_address()), MarketplaceError::Unauthorized);
+
+    // The version is the first field of every layout.
+    let version: u32 = host.state().read_root()?;
+    match version {
+        // The state already has the layout of this module.
+        STATE_VERSION => Ok(()),
+        // Convert older layouts here, e.g.
+        // 1 => {
+        //     let old: StateV1 = host.state().read_root()?;
+        //     let new = State { version: STATE_VERSION, .. };
+        //     host.state_mut().write_root(&new);
+        //     Ok(())
+        // }
+        _ => Err(MarketplaceError::UnsupportedStateVersion),
+    }
+}
 
 // // View function to get all product listings
+// Prefer `view_product_listings_page`, this view gets more expensive with
+// every listing.
 #[receive(contract = "gonana_marketplace", name = "view_product_listings", return_value = "Vec<ProductListing>")]
 fn view_product_listings(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Vec<ProductListing>> {
     let state = host.state();
@@ -686,6 +3572,7 @@ fn view_product_listings(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveRe
 }
 
 // // View function to get all orders
+// Prefer `view_orders_page`, this view gets more expensive with every order.
 #[receive(contract = "gonana_marketplace", name = "view_orders", return_value = "Vec<Order>")]
 fn view_orders(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Vec<Order>> {
     let state = host.state();
@@ -693,6 +3580,220 @@ fn view_orders(_ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Vec<O
     Ok(orders)
 }
 
+/// View function to get all orders placed by a buyer_id.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "view_buyer_orders", parameter = "String", return_value = "Vec<Order>")]
+fn view_buyer_orders(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<Order>, MarketplaceError> {
+    let buyer_id: String = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    let count = state.buyer_order_count.get(&buyer_id).map_or(0, |count| *count);
+    let orders = (0..count)
+        .filter_map(|seq| state.buyer_orders.get(&(buyer_id.clone(), seq)))
+        .filter_map(|id| state.orders.get(&id).map(|order| order.clone()))
+        .collect();
+    Ok(orders)
+}
+
+/// View function to get a single product listing.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The product is not found.
+#[receive(contract = "gonana_marketplace", name = "view_product", parameter = "String", return_value = "ProductListing")]
+fn view_product(ctx: &ReceiveContext, host: &Host<State>) -> Result<ProductListing, MarketplaceError> {
+    let product_id: String = ctx.parameter_cursor().get()?;
+    let listing = host.state().product_listings.get(&product_id).ok_or(MarketplaceError::ProductNotFound)?;
+    Ok(listing.clone())
+}
+
+/// View function to get a single order.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - The order is not found.
+#[receive(contract = "gonana_marketplace", name = "view_order", parameter = "OrderId", return_value = "Order")]
+fn view_order(ctx: &ReceiveContext, host: &Host<State>) -> Result<Order, MarketplaceError> {
+    let order_id: OrderId = ctx.parameter_cursor().get()?;
+    let order = host.state().orders.get(&order_id).ok_or(MarketplaceError::OrderNotFound)?;
+    Ok(order.clone())
+}
+
+/// Authorise an account to act for a merchant_id, or revoke it. Only an admin
+/// can register a new merchant_id; afterwards its accounts can manage the
+/// accounts of the merchant as well.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+/// - Sender is neither an admin nor an account of the merchant.
+#[receive(contract = "gonana_marketplace", name = "update_merchant_accounts", parameter = "UpdateMerchantAccountParameter", mutable, enable_logger)]
+fn update_merchant_accounts(
+    ctx: &ReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let parameter: UpdateMerchantAccountParameter = ctx.parameter_cursor().get()?;
+    internal_update_merchant_accounts(host, parameter, ctx.sender(), logger)
+}
+
+// internal function shared by `update_merchant_accounts` and the permit message
+fn internal_update_merchant_accounts(
+    host: &mut Host<State>,
+    parameter: UpdateMerchantAccountParameter,
+    sender: Address,
+    logger: &mut impl HasLogger,
+) -> Result<(), MarketplaceError> {
+    let (state, builder) = host.state_and_builder();
+    ensure!(
+        state.has_role(&sender, Role::Admin) || state.is_merchant_account(&parameter.merchant_id, &sender),
+        MarketplaceError::Unauthorized
+    );
+
+    match parameter.update {
+        WhitelistUpdate::Add => {
+            state
+                .merchants
+                .entry(parameter.merchant_id.clone())
+                .or_insert_with(|| builder.new_set())
+                .insert(parameter.account);
+        }
+        WhitelistUpdate::Remove => {
+            if let Some(mut accounts) = state.merchants.get_mut(&parameter.merchant_id) {
+                accounts.remove(&parameter.account);
+            }
+        }
+    }
+
+    logger.log(&Event::MerchantAccountUpdated(MerchantAccountEvent {
+        merchant_id: parameter.merchant_id,
+        update:      parameter.update,
+        account:     parameter.account,
+    }))?;
+    Ok(())
+}
+
+/// View function to get the accounts authorised for a merchant_id.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "view_merchant_accounts", parameter = "String", return_value = "Vec<AccountAddress>")]
+fn view_merchant_accounts(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<AccountAddress>, MarketplaceError> {
+    let merchant_id: String = ctx.parameter_cursor().get()?;
+    let accounts = match host.state().merchants.get(&merchant_id) {
+        Some(accounts) => accounts.iter().map(|account| *account).collect(),
+        None => Vec::new(),
+    };
+    Ok(accounts)
+}
+
+/// View function to get the active product listings of a merchant_id.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "view_merchant_products", parameter = "String", return_value = "Vec<ProductListing>")]
+fn view_merchant_products(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<ProductListing>, MarketplaceError> {
+    let merchant_id: String = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    let listings = match state.merchant_products.get(&merchant_id) {
+        Some(product_ids) => product_ids
+            .iter()
+            .filter_map(|id| state.product_listings.get(&id).map(|listing| listing.clone()))
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(listings)
+}
+
+/// Collects a page of at most `limit` items selected from `items`, which
+/// start at position `cursor`. At most `MAX_PAGE_SCAN` items are looked at.
+/// Returns the page and the position after the last scanned item, or `None`
+/// when no items are left.
+fn paginate<T, R>(
+    items: impl Iterator<Item = T>,
+    cursor: u64,
+    limit: u32,
+    mut select: impl FnMut(T) -> Option<R>,
+) -> (Vec<R>, Option<u64>) {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+    let mut items = items.peekable();
+    let mut page = Vec::new();
+    let mut position = cursor;
+    for item in items.by_ref().take(MAX_PAGE_SCAN as usize) {
+        position += 1;
+        if let Some(selected) = select(item) {
+            page.push(selected);
+            if page.len() == limit {
+                break;
+            }
+        }
+    }
+    let next_cursor = items.peek().map(|_| position);
+    (page, next_cursor)
+}
+
+/// View function to get a page of the product listings, optionally filtered
+/// by state and merchant. Listings are returned in the order they were listed
+/// and the cursor is the position of the next listing to look at.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "view_product_listings_page", parameter = "ViewProductListingsParameter", return_value = "ProductListingsPage")]
+fn view_product_listings_page(ctx: &ReceiveContext, host: &Host<State>) -> Result<ProductListingsPage, MarketplaceError> {
+    let parameter: ViewProductListingsParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+
+    let items = parameter.cursor..state.next_listing_seq;
+    let (listings, next_cursor) = paginate(items, parameter.cursor, parameter.limit, |seq| {
+        let product_id = state.listing_ids.get(&seq)?;
+        let listing = state.product_listings.get(&*product_id)?;
+        let state_matches = parameter.state.as_ref().is_none_or(|s| *s == listing.state);
+        let merchant_matches = parameter.merchant_id.as_ref().is_none_or(|m| *m == listing.merchant_id);
+        (state_matches && merchant_matches).then(|| listing.clone())
+    });
+    Ok(ProductListingsPage {
+        listings,
+        next_cursor,
+    })
+}
+
+/// View function to get a page of the orders, optionally filtered by status
+/// and buyer_id. Orders are returned in the order they were placed. The cursor
+/// is the id of the next order to look at, or with a buyer_id the position of
+/// the next order of the buyer.
+///
+/// It rejects if:
+/// - It fails to parse the parameter.
+#[receive(contract = "gonana_marketplace", name = "view_orders_page", parameter = "ViewOrdersParameter", return_value = "OrdersPage")]
+fn view_orders_page(ctx: &ReceiveContext, host: &Host<State>) -> Result<OrdersPage, MarketplaceError> {
+    let parameter: ViewOrdersParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+
+    let select = |order_id: OrderId| {
+        let order = state.orders.get(&order_id)?;
+        parameter.status.is_none_or(|status| status == order.status).then(|| order.clone())
+    };
+    let (orders, next_cursor) = match &parameter.buyer_id {
+        Some(buyer_id) => {
+            let count = state.buyer_order_count.get(buyer_id).map_or(0, |count| *count);
+            let items = parameter.cursor..count;
+            paginate(items, parameter.cursor, parameter.limit, |seq| {
+                select(*state.buyer_orders.get(&(buyer_id.clone(), seq))?)
+            })
+        }
+        None => {
+            let items = parameter.cursor..state.next_order_id;
+            paginate(items, parameter.cursor, parameter.limit, select)
+        }
+    };
+    Ok(OrdersPage {
+        orders,
+        next_cursor,
+    })
+}
+
+
+// The schema is built by ./build-modules.sh
 
 //list_product
 //concordium-client contract update  gonana_marketplace_instance --entrypoint list_product --parameter-json ./list_product.json --schema ./schema.bin --sender TimConcordiumWallet  --energy 2000 --grpc-port 20000 --grpc-ip node.testnet.concordium.com
@@ -0,0 +1,177 @@
+//! Tests for the auctions of the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that a higher bid is escrowed and the outbid bidder gets their bid
+/// back, and that a bid must exceed the highest bid by the minimum increment.
+#[test]
+fn test_bid_refunds_outbid_bidder() {
+    let mut setup = initialize_chain_and_contract();
+    list_auction(&mut setup);
+
+    // The reserve price is the price of both units.
+    let error = rejected_with(bid(&mut setup, CHARLIE, Amount::from_ccd(19)));
+    assert_eq!(error, MarketplaceError::BidTooLow);
+    bid(&mut setup, CHARLIE, Amount::from_ccd(20)).expect("Bid");
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(20)));
+
+    let error = rejected_with(bid(&mut setup, DAVE, Amount::from_micro_ccd(20_500_000)));
+    assert_eq!(error, MarketplaceError::BidTooLow);
+
+    let charlie_before = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+    let update = bid(&mut setup, DAVE, Amount::from_ccd(25)).expect("Bid");
+    let charlie_after = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+
+    assert_eq!(charlie_after, charlie_before + Amount::from_ccd(20));
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(25)));
+    assert_eq!(deserialize_update_events(&setup, &update), [
+        Event::BidPlaced(BidEvent {
+            product_id: PRODUCT_ID.to_string(),
+            bidder:     DAVE,
+            amount:     Amount::from_ccd(25),
+        }),
+        Event::BidRefunded(BidEvent {
+            product_id: PRODUCT_ID.to_string(),
+            bidder:     CHARLIE,
+            amount:     Amount::from_ccd(20),
+        }),
+    ]);
+}
+
+/// Test that an auction can only be settled after its end, and that the
+/// winning bid becomes the escrow of an order of all units.
+#[test]
+fn test_settle_auction() {
+    let mut setup = initialize_chain_and_contract();
+    list_auction(&mut setup);
+    bid(&mut setup, CHARLIE, Amount::from_ccd(20)).expect("Bid");
+
+    let error = rejected_with(settle_auction(&mut setup));
+    assert_eq!(error, MarketplaceError::AuctionNotEnded);
+
+    setup.chain.tick_block_time(Duration::from_hours(1)).expect("Tick block time");
+    let error = rejected_with(bid(&mut setup, DAVE, Amount::from_ccd(25)));
+    assert_eq!(error, MarketplaceError::AuctionEnded);
+
+    let order_id: OrderId = settle_auction(&mut setup)
+        .expect("Settle auction")
+        .parse_return_value()
+        .expect("OrderId return value");
+    let order = view_order(&mut setup, order_id);
+    assert_eq!(order.status, OrderStatus::Escrowed);
+    assert_eq!(order.quantity, 2);
+    assert_eq!(order.amount, Amount::from_ccd(20));
+    assert_eq!(order.buyer_address, Some(CHARLIE));
+
+    let error = rejected_with(settle_auction(&mut setup));
+    assert_eq!(error, MarketplaceError::InvalidProductState);
+}
+
+/// Test that an auction can only be cancelled before the first bid.
+#[test]
+fn test_cancel_auction() {
+    let mut setup = initialize_chain_and_contract();
+    list_auction(&mut setup);
+    bid(&mut setup, CHARLIE, Amount::from_ccd(20)).expect("Bid");
+
+    let error = rejected_with(cancel_product(&mut setup));
+    assert_eq!(error, MarketplaceError::AuctionHasBids);
+
+    let mut setup = initialize_chain_and_contract();
+    list_auction(&mut setup);
+    cancel_product(&mut setup).expect("Cancel product");
+}
+
+/// Test that refunding the order of an auction cancels the listing instead of
+/// listing the auctioned units again.
+#[test]
+fn test_refund_auction_order_cancels_listing() {
+    let mut setup = initialize_chain_and_contract();
+    list_auction(&mut setup);
+    bid(&mut setup, CHARLIE, Amount::from_ccd(20)).expect("Bid");
+    setup.chain.tick_block_time(Duration::from_hours(1)).expect("Tick block time");
+    let order_id: OrderId = settle_auction(&mut setup)
+        .expect("Settle auction")
+        .parse_return_value()
+        .expect("OrderId return value");
+
+    let update = update(&mut setup, BOB, "refund_order", &RefundOrderParameter {
+        order_id,
+        merchant_id: MERCHANT_ID.to_string(),
+    })
+    .expect("Refund order");
+
+    let events = deserialize_update_events(&setup, &update);
+    assert!(events.contains(&Event::ProductCancelled(ProductCancelledEvent {
+        product_id: PRODUCT_ID.to_string(),
+    })));
+    let listings: Vec<ProductListing> = view(&mut setup, "view_merchant_products", &MERCHANT_ID.to_string())
+        .expect("View merchant products")
+        .parse_return_value()
+        .expect("Listings return value");
+    assert!(listings.is_empty());
+    let error = rejected_with(settle_auction(&mut setup));
+    assert_eq!(error, MarketplaceError::InvalidProductState);
+}
+
+/// Test that an auction whose reserve price overflows cannot be listed.
+#[test]
+fn test_list_auction_reserve_overflow() {
+    let mut setup = initialize_chain_and_contract();
+    let end = setup.chain.block_time().checked_add(Duration::from_hours(1)).expect("Valid timestamp");
+    let parameter = ListProductParameter::new(
+        PRODUCT_ID.to_string(),
+        Amount::from_micro_ccd(u64::MAX),
+        Currency::Ccd,
+        2,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    )
+    .with_auction(Amount::from_ccd(1), end);
+
+    let error = rejected_with(update(&mut setup, BOB, "list_product", &parameter));
+    assert_eq!(error, MarketplaceError::InvalidPrice);
+}
+
+/// The parameter Bob auctions two units of `PRODUCT_ID` with, ending one hour
+/// after the current block time with a minimum increment of 1 CCD.
+fn auction_parameter(setup: &Setup) -> ListProductParameter {
+    let end = setup.chain.block_time().checked_add(Duration::from_hours(1)).expect("Valid timestamp");
+    list_product_parameter(2).with_auction(Amount::from_ccd(1), end)
+}
+
+/// Let Bob auction two units of `PRODUCT_ID`.
+fn list_auction(setup: &mut Setup) {
+    update(setup, BOB, "list_product", &auction_parameter(setup)).expect("List auction");
+}
+
+/// Bid on `PRODUCT_ID` for the buyer_id "bidder".
+fn bid(
+    setup: &mut Setup,
+    bidder: AccountAddress,
+    amount: Amount,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update_with_amount(setup, bidder, "bid", &BidParameter {
+        product_id: PRODUCT_ID.to_string(),
+        buyer_id:   "bidder".to_string(),
+    }, amount)
+}
+
+/// Settle the auction of `PRODUCT_ID`, sent by Dave.
+fn settle_auction(setup: &mut Setup) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, DAVE, "settle_auction", &SettleAuctionParameter {
+        product_id: PRODUCT_ID.to_string(),
+    })
+}
+
+/// Let Bob cancel `PRODUCT_ID`.
+fn cancel_product(setup: &mut Setup) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, BOB, "cancel_product", &CancelProductParameter {
+        product_id:  PRODUCT_ID.to_string(),
+        merchant_id: MERCHANT_ID.to_string(),
+    })
+}
@@ -0,0 +1,135 @@
+//! Tests for the batch entrypoints of the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// An account that does not exist on the chain, so payouts to it fail.
+const MISSING: AccountAddress = AccountAddress([9; 32]);
+
+/// Test that in `AllOrNothing` mode a failing product rejects the batch and
+/// none of the products is listed.
+#[test]
+fn test_list_products_all_or_nothing() {
+    let mut setup = initialize_chain_and_contract();
+    let products = vec![product("a", 5), product("b", 0)];
+
+    let error = rejected_with(list_products(&mut setup, BatchMode::AllOrNothing, products));
+    assert_eq!(error, MarketplaceError::InvalidQuantity);
+    let error = rejected_with(view(&mut setup, "view_product", &"a".to_string()));
+    assert_eq!(error, MarketplaceError::ProductNotFound);
+}
+
+/// Test that in `BestEffort` mode a failing product is reported and the
+/// others are listed.
+#[test]
+fn test_list_products_best_effort() {
+    let mut setup = initialize_chain_and_contract();
+    let products = vec![product("a", 5), product("b", 0), product("a", 5)];
+
+    let results: Vec<BatchItemResult> = list_products(&mut setup, BatchMode::BestEffort, products)
+        .expect("List products")
+        .parse_return_value()
+        .expect("Results return value");
+    assert_eq!(results, [
+        BatchItemResult::Ok,
+        BatchItemResult::Failed(MarketplaceError::InvalidQuantity),
+        BatchItemResult::Failed(MarketplaceError::ProductAlreadyExists),
+    ]);
+    view(&mut setup, "view_product", &"a".to_string()).expect("Product a is listed");
+    let error = rejected_with(view(&mut setup, "view_product", &"b".to_string()));
+    assert_eq!(error, MarketplaceError::ProductNotFound);
+}
+
+/// Test that in `AllOrNothing` mode an order that cannot be confirmed rejects
+/// the batch and none of the orders is confirmed.
+#[test]
+fn test_confirm_orders_all_or_nothing() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 1);
+
+    let error = rejected_with(confirm_orders(&mut setup, BatchMode::AllOrNothing, vec![order_id, 99]));
+    assert_eq!(error, MarketplaceError::OrderNotFound);
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Escrowed);
+}
+
+/// Test that in `BestEffort` mode orders that cannot be confirmed, also for a
+/// failing payout, are reported and left unchanged, and the others are
+/// confirmed.
+#[test]
+fn test_confirm_orders_best_effort() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let unpayable = ListProductParameter::new(
+        "unpayable".to_string(),
+        PRICE,
+        Currency::Ccd,
+        5,
+        "bag".to_string(),
+        Some(MISSING),
+        MERCHANT_ID.to_string(),
+    );
+    update(&mut setup, BOB, "list_product", &unpayable).expect("List product");
+
+    let order_id = place_order(&mut setup, 1);
+    let mut parameter = place_order_parameter(1);
+    parameter.product_id = "unpayable".to_string();
+    let unpaid_id: OrderId = update_with_amount(&mut setup, CHARLIE, "place_order", &parameter, PRICE)
+        .expect("Place order")
+        .parse_return_value()
+        .expect("OrderId return value");
+
+    let results: Vec<BatchItemResult> =
+        confirm_orders(&mut setup, BatchMode::BestEffort, vec![unpaid_id, 99, order_id])
+            .expect("Confirm orders")
+            .parse_return_value()
+            .expect("Results return value");
+    assert_eq!(results, [
+        BatchItemResult::Failed(MarketplaceError::TransferError),
+        BatchItemResult::Failed(MarketplaceError::OrderNotFound),
+        BatchItemResult::Ok,
+    ]);
+    assert_eq!(view_order(&mut setup, unpaid_id).status, OrderStatus::Escrowed);
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Confirmed);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(PRICE));
+}
+
+/// The parameter Bob lists `quantity` units of a product with, priced in CCD
+/// and paid out to Bob.
+fn product(product_id: &str, quantity: u64) -> ListProductParameter {
+    ListProductParameter::new(
+        product_id.to_string(),
+        PRICE,
+        Currency::Ccd,
+        quantity,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    )
+}
+
+/// Let Bob list several products.
+fn list_products(
+    setup: &mut Setup,
+    mode: BatchMode,
+    products: Vec<ListProductParameter>,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, BOB, "list_products", &ListProductsParameter {
+        mode,
+        products,
+    })
+}
+
+/// Let Charlie confirm several orders.
+fn confirm_orders(
+    setup: &mut Setup,
+    mode: BatchMode,
+    order_ids: Vec<OrderId>,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, CHARLIE, "confirm_orders", &ConfirmOrdersParameter {
+        mode,
+        order_ids,
+    })
+}
@@ -0,0 +1,374 @@
+//! Helpers shared by the tests of the `gonana_marketplace` contract.
+#![allow(dead_code)]
+use concordium_rust_sdk::id::types::AccountKeys;
+use concordium_cis2::*;
+use concordium_smart_contract_testing::*;
+use concordium_std::{to_bytes, AccountSignatures, Serial};
+use gonana_concordium_smart_contract::*;
+
+/// The tests accounts. Alice instantiates the contract and is its admin,
+/// Bob is an account of the merchant `MERCHANT_ID`, Charlie is a buyer and
+/// Dave sponsors transactions.
+pub const ALICE: AccountAddress = AccountAddress([0; 32]);
+pub const ALICE_ADDR: Address = Address::Account(ALICE);
+pub const BOB: AccountAddress = AccountAddress([1; 32]);
+pub const BOB_ADDR: Address = Address::Account(BOB);
+pub const CHARLIE: AccountAddress = AccountAddress([2; 32]);
+pub const CHARLIE_ADDR: Address = Address::Account(CHARLIE);
+pub const DAVE: AccountAddress = AccountAddress([3; 32]);
+pub const DAVE_ADDR: Address = Address::Account(DAVE);
+
+/// The merchant_id Bob is registered for.
+pub const MERCHANT_ID: &str = "farm";
+
+/// The product listed by `list_product`.
+pub const PRODUCT_ID: &str = "cocoa";
+
+/// Price of one unit of `PRODUCT_ID`.
+pub const PRICE: Amount = Amount::from_ccd(10);
+
+/// Initial balance of the accounts.
+pub const ACC_INITIAL_BALANCE: Amount = Amount::from_ccd(100_000);
+
+/// A signer for all the transactions.
+pub const SIGNER: Signer = Signer::with_one_key();
+
+/// The chain and the marketplace, with the keys of Bob and Charlie for
+/// signing permit messages.
+pub struct Setup {
+    pub chain:        Chain,
+    pub marketplace:  ContractAddress,
+    pub module:       ModuleReference,
+    pub bob_keys:     AccountKeys,
+    pub charlie_keys: AccountKeys,
+}
+
+/// Setup chain and contract, and register Bob as account of `MERCHANT_ID`.
+pub fn initialize_chain_and_contract() -> Setup {
+    let mut chain = Chain::new();
+    let mut rng = rand::thread_rng();
+    let bob_keys = AccountKeys::singleton(&mut rng);
+    let charlie_keys = AccountKeys::singleton(&mut rng);
+    let balance = AccountBalance::new(ACC_INITIAL_BALANCE, Amount::zero(), Amount::zero())
+        .expect("Valid balance");
+
+    // Create some accounts on the chain.
+    chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new_with_keys(BOB, balance, (&bob_keys).into()));
+    chain.create_account(Account::new_with_keys(CHARLIE, balance, (&charlie_keys).into()));
+    chain.create_account(Account::new(DAVE, ACC_INITIAL_BALANCE));
+
+    // Load and deploy the module.
+    let module = module_load_v1("concordium-out/module.wasm.v1")
+        .expect("Module exists, run ./build-modules.sh first");
+    let deployment = chain.module_deploy_v1(SIGNER, ALICE, module).expect("Deploy valid module");
+
+    // Initialize the marketplace.
+    let init = chain
+        .contract_init(SIGNER, ALICE, Energy::from(10000), InitContractPayload {
+            amount:    Amount::zero(),
+            mod_ref:   deployment.module_reference,
+            init_name: OwnedContractName::new_unchecked("init_gonana_marketplace".to_string()),
+            param:     OwnedParameter::empty(),
+        })
+        .expect("Initialize contract");
+
+    let mut setup = Setup {
+        chain,
+        marketplace: init.contract_address,
+        module: deployment.module_reference,
+        bob_keys,
+        charlie_keys,
+    };
+    register_merchant_account(&mut setup, MERCHANT_ID, BOB);
+    setup
+}
+
+/// The parameter Bob lists `quantity` units of `PRODUCT_ID` with, priced in
+/// CCD and paid out to Bob.
+pub fn list_product_parameter(quantity: u64) -> ListProductParameter {
+    ListProductParameter::new(
+        PRODUCT_ID.to_string(),
+        PRICE,
+        Currency::Ccd,
+        quantity,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    )
+}
+
+/// The parameter Charlie orders `quantity` units of `PRODUCT_ID` with.
+pub fn place_order_parameter(quantity: u64) -> PlaceOrderParameter {
+    PlaceOrderParameter {
+        product_id: PRODUCT_ID.to_string(),
+        buyer_address: Some(CHARLIE),
+        buyer_id: "charlie".to_string(),
+        amount: PRICE,
+        quantity,
+    }
+}
+
+/// Call an entrypoint of the marketplace from an account.
+pub fn update<P: Serial>(
+    setup: &mut Setup,
+    sender: AccountAddress,
+    entrypoint: &str,
+    parameter: &P,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update_with_amount(setup, sender, entrypoint, parameter, Amount::zero())
+}
+
+/// Call an entrypoint of the marketplace from an account, attaching CCD.
+pub fn update_with_amount<P: Serial>(
+    setup: &mut Setup,
+    sender: AccountAddress,
+    entrypoint: &str,
+    parameter: &P,
+    amount: Amount,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    setup.chain.contract_update(
+        SIGNER,
+        sender,
+        Address::Account(sender),
+        Energy::from(100000),
+        UpdateContractPayload {
+            amount,
+            address: setup.marketplace,
+            receive_name: OwnedReceiveName::new_unchecked(format!("gonana_marketplace.{entrypoint}")),
+            message: OwnedParameter::from_serial(parameter).expect("Parameter"),
+        },
+    )
+}
+
+/// Invoke a view of the marketplace.
+pub fn view<P: Serial>(
+    setup: &mut Setup,
+    entrypoint: &str,
+    parameter: &P,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    setup.chain.contract_invoke(ALICE, ALICE_ADDR, Energy::from(100000), UpdateContractPayload {
+        amount:       Amount::zero(),
+        address:      setup.marketplace,
+        receive_name: OwnedReceiveName::new_unchecked(format!("gonana_marketplace.{entrypoint}")),
+        message:      OwnedParameter::from_serial(parameter).expect("Parameter"),
+    })
+}
+
+/// The error an update rejected with.
+pub fn rejected_with(result: Result<ContractInvokeSuccess, ContractInvokeError>) -> MarketplaceError {
+    result.expect_err("Update rejects").parse_return_value().expect("MarketplaceError return value")
+}
+
+/// Get the order with the given id.
+pub fn view_order(setup: &mut Setup, order_id: OrderId) -> Order {
+    view(setup, "view_order", &order_id)
+        .expect("View order")
+        .parse_return_value()
+        .expect("Order return value")
+}
+
+/// A permit message for the marketplace with the given nonce, expiring one
+/// hour after the current block time.
+pub fn permit_message<P: Serial>(setup: &Setup, nonce: u64, entrypoint: &str, payload: &P) -> PermitMessage {
+    PermitMessage {
+        contract_address: setup.marketplace,
+        nonce,
+        timestamp: setup
+            .chain
+            .block_time()
+            .checked_add(Duration::from_hours(1))
+            .expect("Valid timestamp"),
+        entry_point: OwnedEntrypointName::new_unchecked(entrypoint.to_string()),
+        payload: to_bytes(payload),
+    }
+}
+
+/// The keys of an account that can sign permit messages.
+pub fn keys_of(setup: &Setup, account: AccountAddress) -> &AccountKeys {
+    match account {
+        BOB => &setup.bob_keys,
+        CHARLIE => &setup.charlie_keys,
+        _ => panic!("No keys for account"),
+    }
+}
+
+/// Sign a permit message as CIS-3 specifies, i.e. sign the message hash
+/// returned by `viewMessageHash`.
+pub fn sign_permit(setup: &mut Setup, signer: AccountAddress, message: PermitMessage) -> PermitParam {
+    let mut param = PermitParam {
+        signature: AccountSignatures {
+            sigs: Default::default(),
+        },
+        signer,
+        message,
+    };
+    let message_hash: [u8; 32] = view(setup, "viewMessageHash", &param)
+        .expect("View message hash")
+        .parse_return_value()
+        .expect("Message hash return value");
+    param.signature = keys_of(setup, signer).sign_message(&message_hash);
+    param
+}
+
+/// Deserialize the events logged by the marketplace in an update.
+pub fn deserialize_update_events(setup: &Setup, update: &ContractInvokeSuccess) -> Vec<Event> {
+    update
+        .events()
+        .filter(|(addr, _events)| *addr == setup.marketplace)
+        .flat_map(|(_addr, events)| events.iter().map(|e| e.parse().expect("Deserialize event")))
+        .collect()
+}
+
+/// Get the next nonce of an account.
+pub fn nonce_of(setup: &mut Setup, account: AccountAddress) -> u64 {
+    let nonces: NonceOfQueryResponse = view(setup, "nonceOf", &VecOfAccountAddresses {
+        queries: vec![account],
+    })
+    .expect("View nonce")
+    .parse_return_value()
+    .expect("Nonce return value");
+    nonces.0[0]
+}
+
+/// Authorise an account for a merchant_id.
+pub fn register_merchant_account(setup: &mut Setup, merchant_id: &str, account: AccountAddress) {
+    update(setup, ALICE, "update_merchant_accounts", &UpdateMerchantAccountParameter {
+        merchant_id: merchant_id.to_string(),
+        update: WhitelistUpdate::Add,
+        account,
+    })
+    .expect("Register merchant account");
+}
+
+/// Let Bob list `quantity` units of `PRODUCT_ID`.
+pub fn list_product(setup: &mut Setup, quantity: u64) {
+    update(setup, BOB, "list_product", &list_product_parameter(quantity)).expect("List product");
+}
+
+/// Let Charlie order `quantity` units of `PRODUCT_ID`, paying their price in
+/// CCD.
+pub fn place_order(setup: &mut Setup, quantity: u64) -> OrderId {
+    let price = Amount::from_micro_ccd(PRICE.micro_ccd * quantity);
+    update_with_amount(setup, CHARLIE, "place_order", &place_order_parameter(quantity), price)
+        .expect("Place order")
+        .parse_return_value()
+        .expect("OrderId return value")
+}
+
+/// The parameter type of the `wrap` function of `gona_token`.
+#[derive(Serial)]
+struct WrapParams {
+    to:   Receiver,
+    data: AdditionalData,
+}
+
+/// The parameter type of the `init` function of `gona_token`.
+#[derive(Serial)]
+struct SetMetadataUrlParams {
+    url:  String,
+    hash: Option<[u8; 32]>,
+}
+
+/// The currency of `gona_token`.
+pub fn gona_currency(gona: ContractAddress) -> Currency {
+    Currency::Cis2 {
+        contract: gona,
+        token_id: TokenIdVec(Vec::new()),
+    }
+}
+
+/// Deploy `gona_token`, whitelist it in the marketplace and let Bob list
+/// `PRODUCT_ID` priced in it.
+pub fn initialize_gona_token(setup: &mut Setup) -> ContractAddress {
+    let module = module_load_v1("gona-token/concordium-out/module.wasm.v1")
+        .expect("Module exists, run ./build-modules.sh first");
+    let deployment = setup.chain.module_deploy_v1(SIGNER, ALICE, module).expect("Deploy valid module");
+    let init = setup
+        .chain
+        .contract_init(SIGNER, ALICE, Energy::from(10000), InitContractPayload {
+            amount:    Amount::zero(),
+            mod_ref:   deployment.module_reference,
+            init_name: OwnedContractName::new_unchecked("init_gona_token".to_string()),
+            param:     OwnedParameter::from_serial(&SetMetadataUrlParams {
+                url:  "https://example.com".to_string(),
+                hash: None,
+            })
+            .expect("Init params"),
+        })
+        .expect("Initialize gona_token");
+    let gona = init.contract_address;
+
+    update(setup, ALICE, "update_token_whitelist", &UpdateTokenWhitelistParameter {
+        update:   WhitelistUpdate::Add,
+        contract: gona,
+        token_id: TokenIdVec(Vec::new()),
+    })
+    .expect("Whitelist gona_token");
+    let listing = ListProductParameter::new(
+        PRODUCT_ID.to_string(),
+        PRICE,
+        gona_currency(gona),
+        5,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    );
+    update(setup, BOB, "list_product", &listing).expect("List product");
+    gona
+}
+
+/// Wrap CCD of an account into `gona_token`.
+pub fn wrap_gona(setup: &mut Setup, gona: ContractAddress, account: AccountAddress, amount: Amount) {
+    setup
+        .chain
+        .contract_update(SIGNER, account, Address::Account(account), Energy::from(10000), UpdateContractPayload {
+            amount,
+            address: gona,
+            receive_name: OwnedReceiveName::new_unchecked("gona_token.wrap".to_string()),
+            message: OwnedParameter::from_serial(&WrapParams {
+                to:   Receiver::Account(account),
+                data: AdditionalData::empty(),
+            })
+            .expect("Wrap params"),
+        })
+        .expect("Wrap CCD");
+}
+
+/// Make the marketplace an operator of an account in `gona_token`.
+pub fn add_marketplace_as_operator(setup: &mut Setup, gona: ContractAddress, account: AccountAddress) {
+    let params = UpdateOperatorParams(vec![UpdateOperator {
+        update:   OperatorUpdate::Add,
+        operator: Address::Contract(setup.marketplace),
+    }]);
+    setup
+        .chain
+        .contract_update(SIGNER, account, Address::Account(account), Energy::from(10000), UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      gona,
+            receive_name: OwnedReceiveName::new_unchecked("gona_token.updateOperator".to_string()),
+            message:      OwnedParameter::from_serial(&params).expect("UpdateOperator params"),
+        })
+        .expect("Update operator");
+}
+
+/// Get the `gona_token` balance of an address.
+pub fn gona_balance_of(setup: &mut Setup, gona: ContractAddress, address: Address) -> TokenAmountU64 {
+    let params = BalanceOfQueryParams {
+        queries: vec![BalanceOfQuery {
+            token_id: TokenIdUnit(),
+            address,
+        }],
+    };
+    let invoke = setup
+        .chain
+        .contract_invoke(ALICE, ALICE_ADDR, Energy::from(10000), UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      gona,
+            receive_name: OwnedReceiveName::new_unchecked("gona_token.balanceOf".to_string()),
+            message:      OwnedParameter::from_serial(&params).expect("BalanceOf params"),
+        })
+        .expect("Invoke balanceOf");
+    let rv: BalanceOfQueryResponse<TokenAmountU64> = invoke.parse_return_value().expect("BalanceOf return value");
+    rv.0[0]
+}
@@ -0,0 +1,253 @@
+//! Tests for the escrow of orders in the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that cancelling a product refunds its escrowed orders.
+#[test]
+fn test_cancel_product_refunds_orders() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(20)));
+
+    let update = update(&mut setup, BOB, "cancel_product", &CancelProductParameter {
+        product_id:  PRODUCT_ID.to_string(),
+        merchant_id: MERCHANT_ID.to_string(),
+    })
+    .expect("Cancel product");
+
+    let events = deserialize_update_events(&setup, &update);
+    assert!(events.contains(&Event::Refund(RefundEvent {
+        order_id,
+        buyer_address: Some(CHARLIE),
+        amount: Amount::from_ccd(20),
+    })));
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Refunded);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::zero()));
+}
+
+/// Test that the merchant can refund an order, but the buyer cannot.
+#[test]
+fn test_refund_order() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    let parameter = RefundOrderParameter {
+        order_id,
+        merchant_id: MERCHANT_ID.to_string(),
+    };
+
+    let error = rejected_with(update(&mut setup, CHARLIE, "refund_order", &parameter));
+    assert_eq!(error, MarketplaceError::UnauthorizedMerchant);
+
+    let balance_before = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+    update(&mut setup, BOB, "refund_order", &parameter).expect("Refund order");
+    let balance_after = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+
+    assert_eq!(balance_after, balance_before + Amount::from_ccd(20));
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Refunded);
+
+    // A refunded order cannot be refunded again.
+    let error = rejected_with(update(&mut setup, BOB, "refund_order", &parameter));
+    assert_eq!(error, MarketplaceError::InvalidOrderStatus);
+}
+
+/// Test that the buyer confirming a shipped order pays the merchant.
+#[test]
+fn test_ship_and_confirm_order() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    ship_order(&mut setup, order_id).expect("Mark shipped");
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Shipped);
+
+    let balance_before = setup.chain.account_balance_available(BOB).expect("Bob exists");
+    let update = update(&mut setup, CHARLIE, "confirm_order", &OrderParameter {
+        order_id,
+    })
+    .expect("Confirm order");
+    let balance_after = setup.chain.account_balance_available(BOB).expect("Bob exists");
+
+    let events = deserialize_update_events(&setup, &update);
+    assert_eq!(events, [Event::OrderConfirmed(OrderConfirmedEvent {
+        order_id,
+        payout: Amount::from_ccd(20),
+        fee: Amount::zero(),
+    })]);
+    assert_eq!(balance_after, balance_before + Amount::from_ccd(20));
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Confirmed);
+}
+
+/// Test that only the buyer can confirm an order.
+#[test]
+fn test_confirm_order_unauthorized() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+
+    let error = rejected_with(update(&mut setup, BOB, "confirm_order", &OrderParameter {
+        order_id,
+    }));
+    assert_eq!(error, MarketplaceError::UnauthorizedConfirmation);
+}
+
+/// Test that the merchant can claim the payout of a shipped order once the
+/// confirmation window has passed.
+#[test]
+fn test_claim_payout_after_confirmation_window() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    ship_order(&mut setup, order_id).expect("Mark shipped");
+    let parameter = OrderParameter {
+        order_id,
+    };
+
+    let error = rejected_with(update(&mut setup, BOB, "claim_payout", &parameter));
+    assert_eq!(error, MarketplaceError::DeadlineNotReached);
+
+    // The confirmation deadline is still part of the window.
+    setup.chain.tick_block_time(CONFIRMATION_WINDOW).expect("Advance block time");
+    let error = rejected_with(update(&mut setup, BOB, "claim_payout", &parameter));
+    assert_eq!(error, MarketplaceError::DeadlineNotReached);
+
+    setup.chain.tick_block_time(Duration::from_millis(1)).expect("Advance block time");
+    update(&mut setup, BOB, "claim_payout", &parameter).expect("Claim payout");
+
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Confirmed);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::zero()));
+}
+
+/// Test that the buyer can reclaim an order that was not shipped before its
+/// delivery deadline, and that it cannot be shipped afterwards.
+#[test]
+fn test_reclaim_order_after_delivery_deadline() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    let parameter = OrderParameter {
+        order_id,
+    };
+
+    let error = rejected_with(update(&mut setup, CHARLIE, "reclaim_order", &parameter));
+    assert_eq!(error, MarketplaceError::DeadlineNotReached);
+
+    // The order can still be shipped at its delivery deadline.
+    setup.chain.tick_block_time(DELIVERY_PERIOD).expect("Advance block time");
+    let error = rejected_with(update(&mut setup, CHARLIE, "reclaim_order", &parameter));
+    assert_eq!(error, MarketplaceError::DeadlineNotReached);
+
+    setup.chain.tick_block_time(Duration::from_millis(1)).expect("Advance block time");
+    let error = rejected_with(ship_order(&mut setup, order_id));
+    assert_eq!(error, MarketplaceError::DeadlinePassed);
+
+    update(&mut setup, CHARLIE, "reclaim_order", &parameter).expect("Reclaim order");
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Refunded);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::zero()));
+}
+
+/// Test that a disputed order can only be settled by the arbiter, who splits
+/// the escrow between the buyer and the merchant.
+#[test]
+fn test_dispute_resolution() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    ship_order(&mut setup, order_id).expect("Mark shipped");
+
+    let update_dispute = update(&mut setup, CHARLIE, "open_dispute", &OpenDisputeParameter {
+        order_id,
+        reason: "damaged".to_string(),
+    })
+    .expect("Open dispute");
+    let events = deserialize_update_events(&setup, &update_dispute);
+    assert_eq!(events, [Event::DisputeOpened(DisputeOpenedEvent {
+        order_id,
+        opened_by: CHARLIE_ADDR,
+        reason: "damaged".to_string(),
+    })]);
+
+    // The order is frozen while the dispute is open.
+    let error = rejected_with(update(&mut setup, CHARLIE, "confirm_order", &OrderParameter {
+        order_id,
+    }));
+    assert_eq!(error, MarketplaceError::InvalidOrderStatus);
+
+    let parameter = ResolveDisputeParameter {
+        order_id,
+        buyer_amount: Amount::from_ccd(5),
+    };
+    let error = rejected_with(update(&mut setup, BOB, "resolve_dispute", &parameter));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+
+    update(&mut setup, ALICE, "grantRole", &RoleParameter {
+        address: ALICE_ADDR,
+        role:    Role::Arbiter,
+    })
+    .expect("Grant arbiter role");
+    let update_resolve = update(&mut setup, ALICE, "resolve_dispute", &parameter).expect("Resolve dispute");
+
+    let events = deserialize_update_events(&setup, &update_resolve);
+    assert_eq!(events, [Event::DisputeResolved(DisputeResolvedEvent {
+        order_id,
+        buyer_amount: Amount::from_ccd(5),
+        merchant_amount: Amount::from_ccd(15),
+    })]);
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Resolved);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::zero()));
+}
+
+/// Test that accounts of the merchant can dispute an order, but the wallet
+/// the listing pays out to cannot.
+#[test]
+fn test_open_dispute_by_merchant_account() {
+    let mut setup = initialize_chain_and_contract();
+    let listing = ListProductParameter::new(
+        PRODUCT_ID.to_string(),
+        PRICE,
+        Currency::Ccd,
+        5,
+        "bag".to_string(),
+        Some(DAVE),
+        MERCHANT_ID.to_string(),
+    );
+    update(&mut setup, BOB, "list_product", &listing).expect("List product");
+    let order_id = place_order(&mut setup, 2);
+    let parameter = OpenDisputeParameter {
+        order_id,
+        reason: "unpaid".to_string(),
+    };
+
+    let error = rejected_with(update(&mut setup, DAVE, "open_dispute", &parameter));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+
+    update(&mut setup, BOB, "open_dispute", &parameter).expect("Open dispute");
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Disputed);
+}
+
+/// Test that an account of another merchant cannot ship an order of the
+/// merchant of the listing.
+#[test]
+fn test_mark_shipped_wrong_merchant_id() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+    register_merchant_account(&mut setup, "other", BOB);
+
+    let error = rejected_with(update(&mut setup, BOB, "mark_shipped", &ShipOrderParameter {
+        order_id,
+        merchant_id: "other".to_string(),
+    }));
+    assert_eq!(error, MarketplaceError::UnauthorizedMerchant);
+}
+
+/// Let Bob mark an order as shipped.
+fn ship_order(setup: &mut Setup, order_id: OrderId) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, BOB, "mark_shipped", &ShipOrderParameter {
+        order_id,
+        merchant_id: MERCHANT_ID.to_string(),
+    })
+}
@@ -0,0 +1,134 @@
+//! Tests for the platform fee of the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that only an admin can set the fee and that it is capped at
+/// `MAX_FEE_BPS`.
+#[test]
+fn test_set_fee() {
+    let mut setup = initialize_chain_and_contract();
+
+    let error = rejected_with(set_fee(&mut setup, BOB, 100));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+    let error = rejected_with(set_fee(&mut setup, ALICE, MAX_FEE_BPS + 1));
+    assert_eq!(error, MarketplaceError::InvalidFee);
+
+    let update = set_fee(&mut setup, ALICE, MAX_FEE_BPS).expect("Set fee");
+    assert_eq!(deserialize_update_events(&setup, &update), [Event::FeeUpdated(FeeUpdatedEvent {
+        fee_bps: MAX_FEE_BPS,
+    })]);
+    assert_eq!(view_fees(&mut setup).fee_bps, MAX_FEE_BPS);
+}
+
+/// Test that the fee is deducted from the payout at the rate the order was
+/// placed at, even if the fee changed since, and is kept in the treasury.
+#[test]
+fn test_fee_frozen_in_order() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    set_fee(&mut setup, ALICE, 500).expect("Set fee");
+    let order_id = place_order(&mut setup, 2);
+    assert_eq!(view_order(&mut setup, order_id).fee_bps, 500);
+
+    set_fee(&mut setup, ALICE, 1000).expect("Set fee");
+    let balance_before = setup.chain.account_balance_available(BOB).expect("Bob exists");
+    let update = update(&mut setup, CHARLIE, "confirm_order", &OrderParameter {
+        order_id,
+    })
+    .expect("Confirm order");
+    let balance_after = setup.chain.account_balance_available(BOB).expect("Bob exists");
+
+    assert_eq!(deserialize_update_events(&setup, &update), [Event::OrderConfirmed(OrderConfirmedEvent {
+        order_id,
+        payout: Amount::from_ccd(19),
+        fee: Amount::from_ccd(1),
+    })]);
+    assert_eq!(balance_after, balance_before + Amount::from_ccd(19));
+    assert_eq!(view_fees(&mut setup), FeesView {
+        fee_bps: 1000,
+        accrued: vec![(Currency::Ccd, Amount::from_ccd(1))],
+    });
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(1)));
+}
+
+/// Test that only an admin can withdraw the accrued fees, and no more than
+/// were accrued.
+#[test]
+fn test_withdraw_fees() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    set_fee(&mut setup, ALICE, 500).expect("Set fee");
+    let order_id = place_order(&mut setup, 2);
+    update(&mut setup, CHARLIE, "confirm_order", &OrderParameter {
+        order_id,
+    })
+    .expect("Confirm order");
+
+    let error = rejected_with(withdraw_fees(&mut setup, BOB, Amount::from_ccd(1)));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+    let error = rejected_with(withdraw_fees(&mut setup, ALICE, Amount::from_ccd(2)));
+    assert_eq!(error, MarketplaceError::InsufficientFunds);
+
+    let balance_before = setup.chain.account_balance_available(DAVE).expect("Dave exists");
+    let update = withdraw_fees(&mut setup, ALICE, Amount::from_ccd(1)).expect("Withdraw fees");
+    let balance_after = setup.chain.account_balance_available(DAVE).expect("Dave exists");
+
+    assert_eq!(deserialize_update_events(&setup, &update), [Event::FeesWithdrawn(FeesWithdrawnEvent {
+        currency: Currency::Ccd,
+        receiver: DAVE,
+        amount:   Amount::from_ccd(1),
+    })]);
+    assert_eq!(balance_after, balance_before + Amount::from_ccd(1));
+    assert_eq!(view_fees(&mut setup).accrued, [(Currency::Ccd, Amount::zero())]);
+}
+
+/// Test that a product cannot be listed without a wallet to pay the merchant
+/// out to.
+#[test]
+fn test_list_product_without_wallet() {
+    let mut setup = initialize_chain_and_contract();
+    let parameter = ListProductParameter::new(
+        PRODUCT_ID.to_string(),
+        PRICE,
+        Currency::Ccd,
+        5,
+        "bag".to_string(),
+        None,
+        MERCHANT_ID.to_string(),
+    );
+
+    let error = rejected_with(update(&mut setup, BOB, "list_product", &parameter));
+    assert_eq!(error, MarketplaceError::MissingWallet);
+}
+
+/// Set the platform fee.
+fn set_fee(
+    setup: &mut Setup,
+    sender: AccountAddress,
+    fee_bps: u16,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, sender, "set_fee", &SetFeeParameter {
+        fee_bps,
+    })
+}
+
+/// Withdraw accrued CCD fees to Dave.
+fn withdraw_fees(
+    setup: &mut Setup,
+    sender: AccountAddress,
+    amount: Amount,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(setup, sender, "withdraw_fees", &WithdrawFeesParameter {
+        currency: Currency::Ccd,
+        receiver: DAVE,
+        amount,
+    })
+}
+
+/// Get the platform fee and the accrued fees.
+fn view_fees(setup: &mut Setup) -> FeesView {
+    view(setup, "view_fees", &()).expect("View fees").parse_return_value().expect("FeesView return value")
+}
@@ -0,0 +1,138 @@
+//! Tests for placing orders in the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_cis2::*;
+use concordium_smart_contract_testing::*;
+use concordium_std::to_bytes;
+use gonana_concordium_smart_contract::*;
+
+/// Test that CCD paid above the price of an order is sent back to the buyer.
+#[test]
+fn test_place_order_refunds_excess() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+
+    let balance_before = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+    let parameter = place_order_parameter(2);
+    let update =
+        update_with_amount(&mut setup, CHARLIE, "place_order", &parameter, Amount::from_ccd(25)).expect("Place order");
+    let balance_after = setup.chain.account_balance_available(CHARLIE).expect("Charlie exists");
+    let order_id: OrderId = update.parse_return_value().expect("OrderId return value");
+
+    let events = deserialize_update_events(&setup, &update);
+    assert!(events.contains(&Event::ExcessRefund(ExcessRefundEvent {
+        order_id,
+        to: CHARLIE,
+        amount: Amount::from_ccd(5),
+    })));
+    assert_eq!(balance_after, balance_before - Amount::from_ccd(20) - update.transaction_fee);
+    assert_eq!(view_order(&mut setup, order_id).amount, Amount::from_ccd(20));
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(20)));
+}
+
+/// Test that tokens transferred above the price of an order are sent back to
+/// the buyer.
+#[test]
+fn test_on_receiving_cis2_refunds_excess() {
+    let mut setup = initialize_chain_and_contract();
+    let gona = initialize_gona_token(&mut setup);
+    wrap_gona(&mut setup, gona, CHARLIE, Amount::from_ccd(30));
+
+    let transfer = TransferParams(vec![concordium_cis2::Transfer {
+        token_id: TokenIdUnit(),
+        amount:   TokenAmountU64(Amount::from_ccd(25).micro_ccd),
+        from:     CHARLIE_ADDR,
+        to:       Receiver::Contract(
+            setup.marketplace,
+            OwnedEntrypointName::new_unchecked("onReceivingCIS2".to_string()),
+        ),
+        data:     AdditionalData::from(to_bytes(&place_order_parameter(2))),
+    }]);
+    let update = setup
+        .chain
+        .contract_update(SIGNER, CHARLIE, CHARLIE_ADDR, Energy::from(100000), UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      gona,
+            receive_name: OwnedReceiveName::new_unchecked("gona_token.transfer".to_string()),
+            message:      OwnedParameter::from_serial(&transfer).expect("Transfer params"),
+        })
+        .expect("Transfer gona_token");
+
+    let events = deserialize_update_events(&setup, &update);
+    let order_id = match events.as_slice() {
+        [Event::OrderPlaced(placed), ..] => placed.order_id,
+        _ => panic!("No OrderPlaced event"),
+    };
+    assert!(events.contains(&Event::ExcessRefund(ExcessRefundEvent {
+        order_id,
+        to: CHARLIE,
+        amount: Amount::from_ccd(5),
+    })));
+    let marketplace = setup.marketplace;
+    assert_eq!(gona_balance_of(&mut setup, gona, CHARLIE_ADDR), TokenAmountU64(Amount::from_ccd(10).micro_ccd));
+    assert_eq!(
+        gona_balance_of(&mut setup, gona, Address::Contract(marketplace)),
+        TokenAmountU64(Amount::from_ccd(20).micro_ccd)
+    );
+    assert_eq!(view_order(&mut setup, order_id).amount, Amount::from_ccd(20));
+}
+
+/// Test that a contract paying more than the price is rejected, as the excess
+/// cannot be sent back to a contract, but paying the exact price is accepted.
+#[test]
+fn test_contract_payer_overpaying() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    // Any contract holding CCD can pay, e.g. the marketplace once it escrows
+    // an order.
+    place_order(&mut setup, 3);
+    let payer = Address::Contract(setup.marketplace);
+
+    let error = rejected_with(order_from(&mut setup, payer, Amount::from_ccd(25)));
+    assert_eq!(error, MarketplaceError::InvalidPrice);
+
+    order_from(&mut setup, payer, Amount::from_ccd(20)).expect("Place order");
+}
+
+/// Test that tokens of a contract paying more than the price are rejected.
+#[test]
+fn test_contract_payer_overpaying_cis2() {
+    let mut setup = initialize_chain_and_contract();
+    let gona = initialize_gona_token(&mut setup);
+
+    let update = setup.chain.contract_update(
+        SIGNER,
+        CHARLIE,
+        Address::Contract(gona),
+        Energy::from(100000),
+        UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      setup.marketplace,
+            receive_name: OwnedReceiveName::new_unchecked("gonana_marketplace.onReceivingCIS2".to_string()),
+            message:      OwnedParameter::from_serial(&OnReceivingCis2Params {
+                token_id: TokenIdVec(Vec::new()),
+                amount:   TokenAmountU64(Amount::from_ccd(25).micro_ccd),
+                from:     Address::Contract(ContractAddress::new(100, 0)),
+                data:     AdditionalData::from(to_bytes(&place_order_parameter(2))),
+            })
+            .expect("OnReceivingCIS2 params"),
+        },
+    );
+    assert_eq!(rejected_with(update), MarketplaceError::InvalidPrice);
+}
+
+/// Let a contract order two units of `PRODUCT_ID` for Charlie, paying `amount`
+/// in CCD.
+fn order_from(
+    setup: &mut Setup,
+    payer: Address,
+    amount: Amount,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    setup.chain.contract_update(SIGNER, CHARLIE, payer, Energy::from(100000), UpdateContractPayload {
+        amount,
+        address: setup.marketplace,
+        receive_name: OwnedReceiveName::new_unchecked("gonana_marketplace.place_order".to_string()),
+        message: OwnedParameter::from_serial(&place_order_parameter(2)).expect("Parameter"),
+    })
+}
@@ -0,0 +1,148 @@
+//! Tests for pausing the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_cis2::*;
+use concordium_smart_contract_testing::*;
+use concordium_std::to_bytes;
+use gonana_concordium_smart_contract::*;
+
+/// The auctioned product.
+const LOT_ID: &str = "lot";
+
+/// Test that listing products, placing orders and bidding reject while the
+/// contract is paused, also by permit.
+#[test]
+fn test_paused_rejects_new_business() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    list_auction(&mut setup);
+    let message = permit_message(&setup, 0, "update_product", &UpdateProductParameter {
+        product_id:  PRODUCT_ID.to_string(),
+        merchant_id: MERCHANT_ID.to_string(),
+        amount:      Some(Amount::from_ccd(20)),
+        wallet:      None,
+        hash:        None,
+    });
+    let permit = sign_permit(&mut setup, BOB, message);
+    set_paused(&mut setup, true);
+
+    let listing = ListProductParameter::new(
+        "pepper".to_string(),
+        PRICE,
+        Currency::Ccd,
+        5,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    );
+    let error = rejected_with(update(&mut setup, BOB, "list_product", &listing));
+    assert_eq!(error, MarketplaceError::ContractPaused);
+
+    let error =
+        rejected_with(update_with_amount(&mut setup, CHARLIE, "place_order", &place_order_parameter(1), PRICE));
+    assert_eq!(error, MarketplaceError::ContractPaused);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &permit));
+    assert_eq!(error, MarketplaceError::ContractPaused);
+
+    let error = rejected_with(bid(&mut setup, Amount::from_ccd(20)));
+    assert_eq!(error, MarketplaceError::ContractPaused);
+
+    let error = rejected_with(update(&mut setup, CHARLIE, "onReceivingCIS2", &OnReceivingCis2Params {
+        token_id: TokenIdVec(Vec::new()),
+        amount:   TokenAmountU64(10),
+        from:     CHARLIE_ADDR,
+        data:     AdditionalData::from(to_bytes(&place_order_parameter(1))),
+    }));
+    assert_eq!(error, MarketplaceError::ContractPaused);
+
+    // Unpausing lets business continue.
+    set_paused(&mut setup, false);
+    update(&mut setup, DAVE, "permit", &permit).expect("Permit update_product");
+}
+
+/// Test that escrowed orders can still be refunded, confirmed, disputed and
+/// resolved, and ended auctions settled, while the contract is paused.
+#[test]
+fn test_paused_settles_escrow() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    list_auction(&mut setup);
+    let refunded = place_order(&mut setup, 1);
+    let confirmed = place_order(&mut setup, 1);
+    let disputed = place_order(&mut setup, 1);
+    bid(&mut setup, Amount::from_ccd(20)).expect("Bid");
+    update(&mut setup, ALICE, "grantRole", &RoleParameter {
+        address: ALICE_ADDR,
+        role:    Role::Arbiter,
+    })
+    .expect("Grant arbiter role");
+    set_paused(&mut setup, true);
+
+    update(&mut setup, BOB, "refund_order", &RefundOrderParameter {
+        order_id:    refunded,
+        merchant_id: MERCHANT_ID.to_string(),
+    })
+    .expect("Refund order");
+    update(&mut setup, CHARLIE, "confirm_order", &OrderParameter {
+        order_id: confirmed,
+    })
+    .expect("Confirm order");
+    update(&mut setup, CHARLIE, "open_dispute", &OpenDisputeParameter {
+        order_id: disputed,
+        reason:   "late".to_string(),
+    })
+    .expect("Open dispute");
+    update(&mut setup, ALICE, "resolve_dispute", &ResolveDisputeParameter {
+        order_id:     disputed,
+        buyer_amount: PRICE,
+    })
+    .expect("Resolve dispute");
+
+    setup.chain.tick_block_time(Duration::from_hours(1)).expect("Tick block time");
+    let order_id: OrderId = update(&mut setup, DAVE, "settle_auction", &SettleAuctionParameter {
+        product_id: LOT_ID.to_string(),
+    })
+    .expect("Settle auction")
+    .parse_return_value()
+    .expect("OrderId return value");
+
+    assert_eq!(view_order(&mut setup, refunded).status, OrderStatus::Refunded);
+    assert_eq!(view_order(&mut setup, confirmed).status, OrderStatus::Confirmed);
+    assert_eq!(view_order(&mut setup, disputed).status, OrderStatus::Resolved);
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Escrowed);
+}
+
+/// Pause or unpause the marketplace.
+fn set_paused(setup: &mut Setup, paused: bool) {
+    update(setup, ALICE, "setPaused", &SetPausedParams {
+        paused,
+    })
+    .expect("Set paused");
+}
+
+/// Let Bob auction two units of `LOT_ID`, ending one hour after the current
+/// block time.
+fn list_auction(setup: &mut Setup) {
+    let end = setup.chain.block_time().checked_add(Duration::from_hours(1)).expect("Valid timestamp");
+    let parameter = ListProductParameter::new(
+        LOT_ID.to_string(),
+        PRICE,
+        Currency::Ccd,
+        2,
+        "bag".to_string(),
+        Some(BOB),
+        MERCHANT_ID.to_string(),
+    )
+    .with_auction(Amount::from_ccd(1), end);
+    update(setup, BOB, "list_product", &parameter).expect("List auction");
+}
+
+/// Let Charlie bid on `LOT_ID`.
+fn bid(setup: &mut Setup, amount: Amount) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update_with_amount(setup, CHARLIE, "bid", &BidParameter {
+        product_id: LOT_ID.to_string(),
+        buyer_id:   "charlie".to_string(),
+    }, amount)
+}
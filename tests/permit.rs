@@ -0,0 +1,198 @@
+//! Tests for the `permit` function of the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_cis2::*;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that a sponsor can submit a listing signed by a merchant account.
+#[test]
+fn test_permit_list_product() {
+    let mut setup = initialize_chain_and_contract();
+
+    let message = permit_message(&setup, 0, "list_product", &list_product_parameter(5));
+    let param = sign_permit(&mut setup, BOB, message);
+    let update = update(&mut setup, DAVE, "permit", &param).expect("Permit list_product");
+
+    let events = deserialize_update_events(&setup, &update);
+    assert_eq!(events, [
+        Event::Nonce(NonceEvent {
+            account: BOB,
+            nonce:   0,
+        }),
+        Event::ProductListed(ProductListedEvent {
+            product_id:  PRODUCT_ID.to_string(),
+            merchant_id: MERCHANT_ID.to_string(),
+            currency:    Currency::Ccd,
+            amount:      PRICE,
+            quantity:    5,
+        }),
+    ]);
+    assert_eq!(nonce_of(&mut setup, BOB), 1);
+}
+
+/// Test that `permit` still dispatches the old name `internal_list_product`
+/// for existing clients.
+#[test]
+fn test_permit_old_entrypoint_name() {
+    let mut setup = initialize_chain_and_contract();
+
+    let message = permit_message(&setup, 0, "internal_list_product", &list_product_parameter(5));
+    let param = sign_permit(&mut setup, BOB, message);
+    update(&mut setup, DAVE, "permit", &param).expect("Permit internal_list_product");
+
+    view(&mut setup, "view_product", &PRODUCT_ID.to_string()).expect("Product is listed");
+}
+
+/// Test that a signature of the payload only is not accepted. The signer has
+/// to sign the hash returned by `viewMessageHash`.
+#[test]
+fn test_permit_payload_signature() {
+    let mut setup = initialize_chain_and_contract();
+
+    let message = permit_message(&setup, 0, "list_product", &list_product_parameter(5));
+    let mut param = sign_permit(&mut setup, BOB, message);
+    param.signature = keys_of(&setup, BOB).sign_message(&param.message.payload);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::WrongSignature);
+    assert_eq!(nonce_of(&mut setup, BOB), 0);
+}
+
+/// Test that a message signed for another contract is not accepted.
+#[test]
+fn test_permit_wrong_contract() {
+    let mut setup = initialize_chain_and_contract();
+
+    let mut message = permit_message(&setup, 0, "list_product", &list_product_parameter(5));
+    message.contract_address = ContractAddress::new(setup.marketplace.index + 1, 0);
+    let param = sign_permit(&mut setup, BOB, message);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::WrongContract);
+}
+
+/// Test that an expired message is not accepted.
+#[test]
+fn test_permit_expired() {
+    let mut setup = initialize_chain_and_contract();
+
+    let message = permit_message(&setup, 0, "list_product", &list_product_parameter(5));
+    let param = sign_permit(&mut setup, BOB, message);
+    setup.chain.tick_block_time(Duration::from_hours(1)).expect("Advance block time");
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::Expired);
+}
+
+/// Test that a message cannot be submitted twice.
+#[test]
+fn test_permit_replayed_nonce() {
+    let mut setup = initialize_chain_and_contract();
+
+    let message = permit_message(&setup, 0, "list_product", &list_product_parameter(5));
+    let param = sign_permit(&mut setup, BOB, message);
+    update(&mut setup, DAVE, "permit", &param).expect("Permit list_product");
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::NonceAlreadyUsed);
+    assert_eq!(nonce_of(&mut setup, BOB), 1);
+}
+
+/// Test that a sponsor can place an order paid with tokens of the signer. The
+/// marketplace pulls the price as an operator of the signer and escrows it.
+#[test]
+fn test_permit_place_order_funded() {
+    let mut setup = initialize_chain_and_contract();
+    let gona = initialize_gona_token(&mut setup);
+    wrap_gona(&mut setup, gona, CHARLIE, Amount::from_ccd(100));
+    add_marketplace_as_operator(&mut setup, gona, CHARLIE);
+
+    let message = permit_message(&setup, 0, "place_order", &place_order_parameter(2));
+    let param = sign_permit(&mut setup, CHARLIE, message);
+    let update = update(&mut setup, DAVE, "permit", &param).expect("Permit place_order");
+
+    let order_id = deserialize_update_events(&setup, &update)
+        .into_iter()
+        .find_map(|event| match event {
+            Event::OrderPlaced(event) => Some(event.order_id),
+            _ => None,
+        })
+        .expect("OrderPlaced event");
+    let order = view_order(&mut setup, order_id);
+    assert_eq!(order.status, OrderStatus::Escrowed);
+    assert_eq!(order.currency, gona_currency(gona));
+    assert_eq!(order.amount, Amount::from_ccd(20));
+    assert_eq!(order.buyer_address, Some(CHARLIE));
+
+    // The price moved from Charlie to the marketplace.
+    let marketplace = Address::Contract(setup.marketplace);
+    assert_eq!(gona_balance_of(&mut setup, gona, Address::Account(CHARLIE)), TokenAmountU64(80_000_000));
+    assert_eq!(gona_balance_of(&mut setup, gona, marketplace), TokenAmountU64(20_000_000));
+}
+
+/// Test that a sponsored order is rejected if the marketplace is not an
+/// operator of the signer.
+#[test]
+fn test_permit_place_order_not_operator() {
+    let mut setup = initialize_chain_and_contract();
+    let gona = initialize_gona_token(&mut setup);
+    wrap_gona(&mut setup, gona, CHARLIE, Amount::from_ccd(100));
+
+    let message = permit_message(&setup, 0, "place_order", &place_order_parameter(2));
+    let param = sign_permit(&mut setup, CHARLIE, message);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::BuyerNotFunded);
+}
+
+/// Test that a sponsored order is rejected if the signer does not hold
+/// enough tokens.
+#[test]
+fn test_permit_place_order_not_funded() {
+    let mut setup = initialize_chain_and_contract();
+    let gona = initialize_gona_token(&mut setup);
+    wrap_gona(&mut setup, gona, CHARLIE, Amount::from_ccd(10));
+    add_marketplace_as_operator(&mut setup, gona, CHARLIE);
+
+    let message = permit_message(&setup, 0, "place_order", &place_order_parameter(2));
+    let param = sign_permit(&mut setup, CHARLIE, message);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::BuyerNotFunded);
+    assert_eq!(gona_balance_of(&mut setup, gona, Address::Account(CHARLIE)), TokenAmountU64(10_000_000));
+}
+
+/// Test that a product priced in CCD cannot be ordered by permit, as CCD
+/// cannot be pulled from the signer.
+#[test]
+fn test_permit_place_order_ccd() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+
+    let message = permit_message(&setup, 0, "place_order", &place_order_parameter(2));
+    let param = sign_permit(&mut setup, CHARLIE, message);
+
+    let error = rejected_with(update(&mut setup, DAVE, "permit", &param));
+    assert_eq!(error, MarketplaceError::WrongCurrency);
+}
+
+/// Test that `supportsPermit` reports the entrypoints `permit` dispatches,
+/// but not the old names only kept for existing clients.
+#[test]
+fn test_supports_permit() {
+    let mut setup = initialize_chain_and_contract();
+    let queries = ["list_product", "internal_list_product", "cancel_product", "cancel_order", "setPaused"];
+    let params = SupportsPermitQueryParams {
+        queries: queries.iter().map(|name| OwnedEntrypointName::new_unchecked(name.to_string())).collect(),
+    };
+
+    let rv: SupportsQueryResponse = view(&mut setup, "supportsPermit", &params)
+        .expect("View supportsPermit")
+        .parse_return_value()
+        .expect("SupportsQueryResponse return value");
+    let supported: Vec<bool> =
+        rv.results.iter().map(|result| matches!(result, SupportResult::Support)).collect();
+    assert_eq!(supported, [true, false, true, false, false]);
+}
@@ -0,0 +1,56 @@
+//! Tests for managing the product listings of the `gonana_marketplace`
+//! contract.
+mod common;
+
+use common::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that an account of another merchant cannot update a product of the
+/// merchant of the listing.
+#[test]
+fn test_update_product_wrong_merchant_id() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    register_merchant_account(&mut setup, "other", BOB);
+
+    let error = rejected_with(update(&mut setup, BOB, "update_product", &UpdateProductParameter {
+        product_id:  PRODUCT_ID.to_string(),
+        merchant_id: "other".to_string(),
+        amount:      None,
+        wallet:      None,
+        hash:        None,
+    }));
+    assert_eq!(error, MarketplaceError::UnauthorizedMerchant);
+}
+
+/// Test that an account of another merchant cannot cancel a product of the
+/// merchant of the listing.
+#[test]
+fn test_cancel_product_wrong_merchant_id() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    register_merchant_account(&mut setup, "other", BOB);
+
+    let error = rejected_with(update(&mut setup, BOB, "cancel_product", &CancelProductParameter {
+        product_id:  PRODUCT_ID.to_string(),
+        merchant_id: "other".to_string(),
+    }));
+    assert_eq!(error, MarketplaceError::UnauthorizedMerchant);
+}
+
+/// Test that an account of another merchant cannot set the metadata URL of a
+/// product of the merchant of the listing.
+#[test]
+fn test_set_product_metadata_url_wrong_merchant_id() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    register_merchant_account(&mut setup, "other", BOB);
+
+    let parameter = SetProductMetadataUrlParameter {
+        product_id:   PRODUCT_ID.to_string(),
+        merchant_id:  "other".to_string(),
+        metadata_url: None,
+    };
+    let error = rejected_with(update(&mut setup, BOB, "set_product_metadata_url", &parameter));
+    assert_eq!(error, MarketplaceError::UnauthorizedMerchant);
+}
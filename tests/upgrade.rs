@@ -0,0 +1,62 @@
+//! Tests for upgrading the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_rust_sdk::types::smart_contracts::WasmModule;
+use concordium_smart_contract_testing::*;
+use gonana_concordium_smart_contract::*;
+
+/// Test that an admin can upgrade the marketplace to a newly deployed module
+/// and migrate its state, keeping escrowed orders.
+#[test]
+fn test_upgrade_and_migrate() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 5);
+    let order_id = place_order(&mut setup, 2);
+
+    let deployment = setup.chain.module_deploy_v1(SIGNER, ALICE, second_module()).expect("Deploy valid module");
+    let parameter = UpgradeParams {
+        module:  deployment.module_reference,
+        migrate: Some((OwnedEntrypointName::new_unchecked("migrate".to_string()), OwnedParameter::empty())),
+    };
+
+    let error = rejected_with(update(&mut setup, BOB, "upgrade", &parameter));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+
+    update(&mut setup, ALICE, "upgrade", &parameter).expect("Upgrade");
+    let contract = setup.chain.get_contract(setup.marketplace).expect("Marketplace exists");
+    assert_eq!(contract.module_reference, deployment.module_reference);
+
+    let version: u32 = view(&mut setup, "view_state_version", &())
+        .expect("View state version")
+        .parse_return_value()
+        .expect("Version return value");
+    assert_eq!(version, STATE_VERSION);
+    assert_eq!(view_order(&mut setup, order_id).status, OrderStatus::Escrowed);
+    assert_eq!(setup.chain.contract_balance(setup.marketplace), Some(Amount::from_ccd(20)));
+}
+
+/// Test that `migrate` can only be called by the marketplace itself, i.e. by
+/// `upgrade`.
+#[test]
+fn test_migrate_unauthorized() {
+    let mut setup = initialize_chain_and_contract();
+
+    let error = rejected_with(update(&mut setup, ALICE, "migrate", &()));
+    assert_eq!(error, MarketplaceError::Unauthorized);
+}
+
+/// The marketplace module with an empty custom section appended, so that the
+/// same code can be deployed as a second module.
+fn second_module() -> WasmModule {
+    let mut bytes =
+        std::fs::read("concordium-out/module.wasm.v1").expect("Module exists, run ./build-modules.sh first");
+    // Section id 0, section size 3, name "v2".
+    bytes.extend_from_slice(&[0, 3, 2, b'v', b'2']);
+    // The module is prefixed with its version and the length of the Wasm.
+    let length = u32::try_from(bytes.len() - 8).expect("Module fits");
+    bytes[4..8].copy_from_slice(&length.to_be_bytes());
+    let path = std::env::temp_dir().join("gonana_marketplace_v2.wasm.v1");
+    std::fs::write(&path, bytes).expect("Write module");
+    module_load_v1(path).expect("Second module is valid")
+}
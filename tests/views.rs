@@ -0,0 +1,138 @@
+//! Tests for the views and view types of the `gonana_marketplace` contract.
+mod common;
+
+use common::*;
+use concordium_std::{to_bytes, Timestamp};
+use gonana_concordium_smart_contract::*;
+
+/// Test that a page looks at no more than `MAX_PAGE_SCAN` orders, and that the
+/// orders of a buyer are paged over the orders of the buyer only.
+#[test]
+fn test_view_orders_page() {
+    let mut setup = initialize_chain_and_contract();
+    list_product(&mut setup, 2000);
+
+    // Charlie places the first order and one after `MAX_PAGE_SCAN` orders of
+    // Dave. Both are refunded.
+    let first = place_order(&mut setup, 1);
+    for _ in 0..MAX_PAGE_SCAN {
+        let mut parameter = place_order_parameter(1);
+        parameter.buyer_address = Some(DAVE);
+        parameter.buyer_id = "dave".to_string();
+        update_with_amount(&mut setup, DAVE, "place_order", &parameter, PRICE).expect("Place order");
+    }
+    let last = place_order(&mut setup, 1);
+    for order_id in [first, last] {
+        update(&mut setup, BOB, "refund_order", &RefundOrderParameter {
+            order_id,
+            merchant_id: MERCHANT_ID.to_string(),
+        })
+        .expect("Refund order");
+    }
+
+    let page = view_orders_page(&mut setup, 0, Some(OrderStatus::Refunded), None);
+    assert_eq!(order_ids(&page), [first]);
+    assert_eq!(page.next_cursor, Some(u64::from(MAX_PAGE_SCAN)));
+
+    let page = view_orders_page(&mut setup, u64::from(MAX_PAGE_SCAN), Some(OrderStatus::Refunded), None);
+    assert_eq!(order_ids(&page), [last]);
+    assert_eq!(page.next_cursor, None);
+
+    let page = view_orders_page(&mut setup, 0, None, Some("charlie"));
+    assert_eq!(order_ids(&page), [first, last]);
+    assert_eq!(page.next_cursor, None);
+}
+
+/// Test that the listings are paged in the order they were listed and that
+/// the cursor continues after the last listing looked at.
+#[test]
+fn test_view_product_listings_page() {
+    let mut setup = initialize_chain_and_contract();
+    register_merchant_account(&mut setup, "other", BOB);
+    for (product_id, merchant_id) in [("a", MERCHANT_ID), ("b", "other"), ("c", MERCHANT_ID), ("d", MERCHANT_ID)] {
+        let listing = ListProductParameter::new(
+            product_id.to_string(),
+            PRICE,
+            Currency::Ccd,
+            5,
+            "bag".to_string(),
+            Some(BOB),
+            merchant_id.to_string(),
+        );
+        update(&mut setup, BOB, "list_product", &listing).expect("List product");
+    }
+
+    let page = view_product_listings_page(&mut setup, 0);
+    assert_eq!(page.listings, [view_product(&mut setup, "a"), view_product(&mut setup, "c")]);
+    assert_eq!(page.next_cursor, Some(3));
+
+    let page = view_product_listings_page(&mut setup, 3);
+    assert_eq!(page.listings, [view_product(&mut setup, "d")]);
+    assert_eq!(page.next_cursor, None);
+}
+
+/// Test that `ProductMetadata` is serialized as documented, which is what
+/// off-chain hashes of catalogue entries have to match.
+#[test]
+fn test_product_metadata_layout() {
+    let metadata = ProductMetadata {
+        name:         "Cocoa".to_string(),
+        grade:        "A".to_string(),
+        origin:       "Ondo".to_string(),
+        harvest_date: Timestamp::from_timestamp_millis(1_700_000_000_000),
+        photos:       vec!["p1".to_string()],
+    };
+
+    let mut expected = Vec::new();
+    for field in ["Cocoa", "A", "Ondo"] {
+        expected.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        expected.extend_from_slice(field.as_bytes());
+    }
+    expected.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+    expected.push(1);
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.extend_from_slice(b"p1");
+    assert_eq!(to_bytes(&metadata), expected);
+}
+
+/// Get a page of ten orders.
+fn view_orders_page(
+    setup: &mut Setup,
+    cursor: u64,
+    status: Option<OrderStatus>,
+    buyer_id: Option<&str>,
+) -> OrdersPage {
+    view(setup, "view_orders_page", &ViewOrdersParameter {
+        cursor,
+        limit: 10,
+        status,
+        buyer_id: buyer_id.map(str::to_string),
+    })
+    .expect("View orders page")
+    .parse_return_value()
+    .expect("OrdersPage return value")
+}
+
+/// The ids of the orders of a page.
+fn order_ids(page: &OrdersPage) -> Vec<OrderId> { page.orders.iter().map(|order| order.id).collect() }
+
+/// Get a page of two listings of `MERCHANT_ID`.
+fn view_product_listings_page(setup: &mut Setup, cursor: u64) -> ProductListingsPage {
+    view(setup, "view_product_listings_page", &ViewProductListingsParameter {
+        cursor,
+        limit: 2,
+        state: None,
+        merchant_id: Some(MERCHANT_ID.to_string()),
+    })
+    .expect("View product listings page")
+    .parse_return_value()
+    .expect("ProductListingsPage return value")
+}
+
+/// Get a listing.
+fn view_product(setup: &mut Setup, product_id: &str) -> ProductListing {
+    view(setup, "view_product", &product_id.to_string())
+        .expect("View product")
+        .parse_return_value()
+        .expect("ProductListing return value")
+}